@@ -0,0 +1,164 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+
+/// Strategy for choosing single API client from the group members connected to current Node
+/// Weights and outstanding events are known only locally, so Nodes having group members are chosen with Round Robin
+#[derive(Clone, Copy, PartialEq)]
+pub enum BalanceStrategy {
+    RoundRobin,
+    Weighted,
+    LeastOutstanding
+}
+
+impl BalanceStrategy {
+    pub fn from_name(name: &str) -> Option<BalanceStrategy> {
+        match name {
+            "round-robin" => Some(BalanceStrategy::RoundRobin),
+            "weighted" => Some(BalanceStrategy::Weighted),
+            "least-outstanding" => Some(BalanceStrategy::LeastOutstanding),
+            _ => None
+        }
+    }
+}
+
+/// Keeping load balancing state for API clients subscribed to groups
+/// so that every group event would be delivered only to one of the group members
+pub struct Balancer {
+    strategy: BalanceStrategy,
+
+    // Round Robin index for each group
+    // Key -> Group Name
+    indexes: BTreeMap<String, usize>,
+
+    // Weights given by API clients during group subscription
+    // Key -> (Group Name, API client Token)
+    weights: BTreeMap<(String, String), u32>,
+
+    // Current weights for smooth weighted round robin
+    // Key -> (Group Name, API client Token)
+    current: BTreeMap<(String, String), i64>,
+
+    // Count of delivered group events which are not yet marked as done
+    // Key -> API client Token
+    outstanding: BTreeMap<String, u64>,
+}
+
+impl Balancer {
+    pub fn new(strategy: BalanceStrategy) -> Balancer {
+        Balancer {
+            strategy,
+            indexes: BTreeMap::new(),
+            weights: BTreeMap::new(),
+            current: BTreeMap::new(),
+            outstanding: BTreeMap::new()
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_weight(&mut self, group: &str, client: &str, weight: u32) {
+        self.weights.insert((String::from(group), String::from(client)), weight);
+    }
+
+    #[inline(always)]
+    pub fn rm_weight(&mut self, group: &str, client: &str) {
+        let key = (String::from(group), String::from(client));
+        self.weights.remove(&key);
+        self.current.remove(&key);
+    }
+
+    #[inline(always)]
+    pub fn weight(&self, group: &str, client: &str) -> u32 {
+        *self.weights.get(&(String::from(group), String::from(client))).unwrap_or(&1)
+    }
+
+    /// Cleaning up everything related to closed API client
+    pub fn rm_client(&mut self, client: &str) {
+        self.weights.retain(|(_, c), _| c != client);
+        self.current.retain(|(_, c), _| c != client);
+        self.outstanding.remove(client);
+    }
+
+    /// Marking given count of group events as processed by API client
+    pub fn done(&mut self, client: &str, count: u64) {
+        let remove = match self.outstanding.get_mut(client) {
+            Some(n) => {
+                *n = n.saturating_sub(count);
+                *n == 0
+            }
+            None => false
+        };
+
+        if remove {
+            self.outstanding.remove(client);
+        }
+    }
+
+    /// Choosing one of the group members for delivering group event
+    /// Members list should be sorted, to keep Round Robin stable
+    pub fn pick(&mut self, group: &str, members: &[String]) -> Option<String> {
+        if members.is_empty() {
+            return None;
+        }
+
+        let client = match self.strategy {
+            BalanceStrategy::RoundRobin => self.pick_round_robin(group, members),
+            BalanceStrategy::Weighted => self.pick_weighted(group, members),
+            BalanceStrategy::LeastOutstanding => self.pick_least_outstanding(group, members),
+        };
+
+        *self.outstanding.entry(client.clone()).or_insert(0) += 1;
+        Some(client)
+    }
+
+    fn pick_round_robin(&mut self, group: &str, members: &[String]) -> String {
+        let index = self.indexes.entry(String::from(group)).or_insert(0);
+        if *index >= members.len() {
+            *index = 0;
+        }
+
+        let i = *index;
+        *index += 1;
+        members[i].clone()
+    }
+
+    /// Smooth weighted round robin, every member is getting events
+    /// proportionally to his weight, without sending them in bursts
+    fn pick_weighted(&mut self, group: &str, members: &[String]) -> String {
+        let mut total: i64 = 0;
+        let mut best: Option<(i64, usize)> = None;
+        for (i, member) in members.iter().enumerate() {
+            let key = (String::from(group), member.clone());
+            let weight = i64::from(*self.weights.get(&key).unwrap_or(&1));
+            let current = self.current.entry(key).or_insert(0);
+            *current += weight;
+            total += weight;
+
+            match best {
+                Some((w, _)) if w >= *current => {}
+                _ => best = Some((*current, i))
+            }
+        }
+
+        let i = best.map(|(_, i)| i).unwrap_or(0);
+        if let Some(current) = self.current.get_mut(&(String::from(group), members[i].clone())) {
+            *current -= total;
+        }
+
+        members[i].clone()
+    }
+
+    fn pick_least_outstanding(&mut self, group: &str, members: &[String]) -> String {
+        let least = members.iter()
+                           .map(|m| *self.outstanding.get(m).unwrap_or(&0))
+                           .min()
+                           .unwrap_or(0);
+
+        // using Round Robin between members with the same outstanding count
+        let candidates: Vec<String> = members.iter()
+                                             .filter(|m| *self.outstanding.get(*m).unwrap_or(&0) == least)
+                                             .cloned()
+                                             .collect();
+        self.pick_round_robin(group, &candidates)
+    }
+}
@@ -0,0 +1,328 @@
+#![allow(dead_code)]
+
+use node::Node;
+use network::Networking;
+use event::Event;
+use graph::{Gossip, SubscriptionType};
+use helper::{Log, NetHelper, NameTrie};
+use queue::Queueing;
+use error::Error;
+use api::{API_SUBSCRIBE, API_UNSUBSCRIBE, API_DONE, API_ACK, API_ERROR, API_READY
+          , API_QUEUE, API_QUEUE_ACK, API_STATS};
+
+/// Single item of subscription request
+#[derive(Clone)]
+pub struct Subscription {
+    pub kind: SubscriptionType,
+    pub name: String,
+    // weight of API client inside the group, used only for groups
+    pub weight: u32
+}
+
+/// Subscribe/Unsubscribe request data sent by API client
+/// Data is a list of items, where each item is
+/// 1 byte subscription type, 4 bytes BigEndian name length and name bytes
+/// Group items are followed by 4 bytes BigEndian weight of API client in that group
+pub struct SubscriptionMessage {
+    pub items: Vec<Subscription>
+}
+
+impl SubscriptionMessage {
+    pub fn from_bytes(data: &[u8]) -> Result<SubscriptionMessage, Error> {
+        let mut items = vec![];
+        let mut offset = 0;
+        while offset < data.len() {
+            let kind = match SubscriptionType::from_byte(data[offset]) {
+                Some(k) => k,
+                None => return Err(Error::Protocol(String::from("Unknown subscription type")))
+            };
+
+            let len = NetHelper::bytes_to_u32(data, offset + 1).unwrap_or(u32::MAX) as usize;
+            if offset + 5 + len > data.len() {
+                return Err(Error::Protocol(String::from("Subscription name is out of data bounds")));
+            }
+
+            let name = match String::from_utf8(Vec::from(&data[(offset + 5)..(offset + 5 + len)])) {
+                Ok(s) => s,
+                Err(_) => return Err(Error::Protocol(String::from("Subscription name is not a valid UTF-8 string")))
+            };
+
+            if name.is_empty() {
+                return Err(Error::Protocol(String::from("Subscription name couldn't be empty")));
+            }
+
+            if kind == SubscriptionType::Event && !NameTrie::is_valid(&name) {
+                return Err(Error::Protocol(String::from("Event name wildcard \">\" could be only the last segment")));
+            }
+            offset += 5 + len;
+
+            let mut weight = 0;
+            if kind == SubscriptionType::Group {
+                let w = match NetHelper::bytes_to_u32(data, offset) {
+                    Ok(w) => w,
+                    Err(_) => return Err(Error::Protocol(String::from("Group weight is out of data bounds")))
+                };
+
+                if w == 0 {
+                    return Err(Error::Protocol(String::from("Group weight should be greater than 0")));
+                }
+
+                weight = w;
+                offset += 4;
+            }
+
+            items.push(Subscription { kind, name, weight });
+        }
+
+        if items.is_empty() {
+            return Err(Error::Protocol(String::from("Subscription list is empty")));
+        }
+
+        Ok(SubscriptionMessage { items })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let size = self.items.iter()
+                             .map(|item| item.name.len() + if item.kind == SubscriptionType::Group { 9 } else { 5 })
+                             .sum();
+        let mut buffer = vec![0; size];
+        let mut offset = 0;
+        for item in &self.items {
+            buffer[offset] = item.kind.to_byte();
+            offset += 1;
+            offset += NetHelper::u32_to_bytes(item.name.len() as u32, &mut buffer, offset);
+            buffer[offset..offset + item.name.len()].copy_from_slice(item.name.as_bytes());
+            offset += item.name.len();
+            if item.kind == SubscriptionType::Group {
+                offset += NetHelper::u32_to_bytes(item.weight, &mut buffer, offset);
+            }
+        }
+
+        buffer
+    }
+}
+
+/// Handling control messages from API clients connected to current Node
+/// Each control message is getting exactly one response, ACK or ERROR
+/// in the same order as they have been sent
+pub trait ApiControl {
+    /// Letting API client know that he is accepted and can send control messages
+    fn api_ready(&mut self, token: &str);
+
+    /// Handling control event sent by API client with given token
+    fn on_api_control(&mut self, token: &str, event: &Event);
+
+    /// Adding or removing subscriptions of API client
+    fn api_subscribe(&mut self, token: &str, items: Vec<Subscription>, subscribe: bool);
+
+    /// Enabling or disabling persistent queue for API client
+    fn api_queue(&mut self, token: &str, event: &Event);
+
+    /// Saving subscriptions of durable API client, so that they would survive Node restart
+    fn api_save_subscriptions(&mut self, token: &str);
+
+    /// Cleaning up subscriptions for closed API connection
+    /// Durable API clients are keeping their subscriptions until they will disable queue
+    fn api_close(&mut self, token: &str);
+
+    /// Sending response for control message back to API client
+    fn api_reply(&mut self, token: &str, name: &str, request: &str, data: Vec<u8>);
+}
+
+impl ApiControl for Node {
+    fn api_ready(&mut self, token: &str) {
+        let self_token = self.token.clone();
+        self.api_reply(token, API_READY, "", self_token.into_bytes());
+
+        // reconnected durable API client is getting everything he missed
+        if self.queue.is_durable(token) {
+            self.queue_replay(token);
+        }
+    }
+
+    fn on_api_control(&mut self, token: &str, event: &Event) {
+        let subscribe = match event.name.as_str() {
+            API_SUBSCRIBE => true,
+            API_UNSUBSCRIBE => false,
+            API_DONE => {
+                // API client is not waiting for response here
+                let count = NetHelper::bytes_to_u32(&event.data, 0).map(u64::from).unwrap_or(1);
+                self.balancer.done(token, count);
+                return;
+            }
+            API_QUEUE => {
+                self.api_queue(token, event);
+                return;
+            }
+            API_QUEUE_ACK => {
+                // API client is not waiting for response here
+                for i in 0..(event.data.len() / 8) {
+                    if let Ok(id) = NetHelper::bytes_to_u64(&event.data, i * 8) {
+                        self.queue.ack(token, id);
+                    }
+                }
+                return;
+            }
+            API_STATS => {
+                let data = self.net_dropped_events.to_bytes();
+                self.api_reply(token, API_STATS, &event.name, data);
+                return;
+            }
+            _ => {
+                self.api_reply(token, API_ERROR, &event.name, Vec::from("Unknown control message"));
+                return;
+            }
+        };
+
+        let message = match SubscriptionMessage::from_bytes(&event.data) {
+            Ok(m) => m,
+            Err(e) => {
+                let reason = e.to_string();
+                Log::warn("Unable to parse subscription message from API client", reason.as_str());
+                self.api_reply(token, API_ERROR, &event.name, reason.into_bytes());
+                return;
+            }
+        };
+
+        self.api_subscribe(token, message.items, subscribe);
+        self.api_reply(token, API_ACK, &event.name, event.data.clone());
+    }
+
+    fn api_subscribe(&mut self, token: &str, items: Vec<Subscription>, subscribe: bool) {
+        let mut changed = false;
+        for item in items {
+            if item.kind == SubscriptionType::Group {
+                if subscribe {
+                    self.balancer.set_weight(&item.name, token, item.weight);
+                } else {
+                    self.balancer.rm_weight(&item.name, token);
+                }
+            }
+
+            changed = if subscribe {
+                self.graph.subscribe_client(token, item.kind, &item.name)
+            } else {
+                self.graph.unsubscribe_client(token, item.kind, &item.name)
+            } || changed;
+        }
+
+        // rest of the tree should know about our new subscriptions
+        if changed {
+            self.gossip_subscriptions();
+        }
+
+        self.api_save_subscriptions(token);
+    }
+
+    fn api_queue(&mut self, token: &str, event: &Event) {
+        let enable = match event.data.first() {
+            Some(flag) => *flag != 0,
+            None => {
+                self.api_reply(token, API_ERROR, &event.name, Vec::from("Queue flag is missing"));
+                return;
+            }
+        };
+
+        if !enable {
+            self.queue.disable(token);
+            self.api_reply(token, API_ACK, &event.name, event.data.clone());
+            return;
+        }
+
+        if !self.queue.enable(token) {
+            self.api_reply(token, API_ERROR, &event.name, Vec::from("Persistent queue is not available on this Node"));
+            return;
+        }
+
+        self.api_save_subscriptions(token);
+        self.api_reply(token, API_ACK, &event.name, event.data.clone());
+    }
+
+    fn api_save_subscriptions(&mut self, token: &str) {
+        if !self.queue.is_durable(token) {
+            return;
+        }
+
+        let items = self.graph.client_subscriptions(token).iter().map(|&(kind, ref name)| {
+            Subscription {
+                kind,
+                name: name.clone(),
+                weight: if kind == SubscriptionType::Group { self.balancer.weight(name, token) } else { 0 }
+            }
+        }).collect();
+
+        let data = SubscriptionMessage { items }.to_bytes();
+        self.queue.save_subscriptions(token, &data);
+    }
+
+    fn api_close(&mut self, token: &str) {
+        if self.queue.is_durable(token) {
+            self.queue.offline(token);
+            return;
+        }
+
+        self.balancer.rm_client(token);
+        if self.graph.rm_client(token) {
+            self.gossip_subscriptions();
+        }
+    }
+
+    fn api_reply(&mut self, token: &str, name: &str, request: &str, data: Vec<u8>) {
+        let ev = Event {
+            name: String::from(name),
+            from: self.token.clone(),
+            target: String::from(request),
+            data,
+            ..Event::default()
+        };
+        self.send_to(&[String::from(token)], &ev);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(kind: u8, name: &[u8]) -> Vec<u8> {
+        let mut data = vec![kind, 0, 0, 0, name.len() as u8];
+        data.extend_from_slice(name);
+        data
+    }
+
+    fn error_of(data: Vec<u8>) -> String {
+        match SubscriptionMessage::from_bytes(&data) {
+            Ok(_) => panic!("Subscription message should be invalid"),
+            Err(Error::Protocol(msg)) => msg,
+            Err(e) => panic!("Unexpected error kind -> {}", e)
+        }
+    }
+
+    #[test]
+    fn parse_items() {
+        let mut data = item(1, b"docker.>");
+        data.extend(item(3, b"workers"));
+        data.extend_from_slice(&[0, 0, 0, 5]);
+
+        let msg = SubscriptionMessage::from_bytes(&data).unwrap();
+        assert_eq!(msg.items.len(), 2);
+        assert!(msg.items[0].kind == SubscriptionType::Event && msg.items[0].name == "docker.>");
+        assert!(msg.items[1].kind == SubscriptionType::Group && msg.items[1].weight == 5);
+        assert_eq!(msg.to_bytes(), data);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error_of(vec![]), "Subscription list is empty");
+        assert_eq!(error_of(item(9, b"name")), "Unknown subscription type");
+        assert_eq!(error_of(vec![1, 0, 0, 0, 10, b'a']), "Subscription name is out of data bounds");
+        assert_eq!(error_of(vec![1, 0, 0]), "Subscription name is out of data bounds");
+        assert_eq!(error_of(item(1, &[0xff, 0xfe])), "Subscription name is not a valid UTF-8 string");
+        assert_eq!(error_of(item(2, b"")), "Subscription name couldn't be empty");
+        assert_eq!(error_of(item(1, b"a.>.b")), "Event name wildcard \">\" could be only the last segment");
+        assert_eq!(error_of(item(3, b"group")), "Group weight is out of data bounds");
+
+        let mut data = item(3, b"group");
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        assert_eq!(error_of(data), "Group weight should be greater than 0");
+    }
+}
@@ -0,0 +1,50 @@
+#![allow(dead_code)]
+
+use node::Node;
+use network::Networking;
+use event::Event;
+use graph::SubscriptionType;
+use queue::Queueing;
+
+/// Delivering events to API clients connected to current Node
+pub trait ApiDelivery {
+    /// Sending event to every local API client subscribed to it
+    /// If event is targeting a group, only one of the group members is getting it
+    /// Returns count of API clients who got this event
+    fn deliver_local(&mut self, event: &Event) -> usize;
+}
+
+impl ApiDelivery for Node {
+    fn deliver_local(&mut self, event: &Event) -> usize {
+        let mut clients = self.graph.event_clients(event);
+
+        // if event path is going to another group member Node
+        // then we are only on the way, and our group members shouldn't get it
+        if !event.target.is_empty() && !self.graph.group_in_path(&event.target, &event.path) {
+            let members = self.graph.clients_for(SubscriptionType::Group, &event.target);
+            if let Some(client) = self.balancer.pick(&event.target, &members) {
+                clients.insert(client);
+            }
+        }
+
+        if clients.is_empty() {
+            return 0;
+        }
+
+        let count = clients.len();
+
+        // durable API clients are getting events through their persistent queue
+        let (durable, clients): (Vec<String>, Vec<String>) = clients.into_iter()
+                                                                     .partition(|c| self.queue.is_durable(c));
+        if !durable.is_empty() {
+            if let Some(data) = event.to_raw() {
+                for client in durable {
+                    self.queue_event(&client, &data);
+                }
+            }
+        }
+
+        self.send_to(&clients, event);
+        count
+    }
+}
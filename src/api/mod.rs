@@ -0,0 +1,35 @@
+mod control;
+mod delivery;
+mod balancer;
+
+pub use self::control::{ApiControl, Subscription, SubscriptionMessage};
+pub use self::delivery::ApiDelivery;
+pub use self::balancer::{Balancer, BalanceStrategy};
+
+/// Prefix for the reserved event names used for control messages
+/// API clients are not allowed to publish events with this prefix
+pub const API_EVENT_PREFIX: &str = "_ts_";
+/// API client subscribing to events, channels, groups or tags
+pub const API_SUBSCRIBE: &str = "_ts_subscribe";
+/// API client removing his subscriptions
+pub const API_UNSUBSCRIBE: &str = "_ts_unsubscribe";
+/// API client finished processing group events, data is optional 4 bytes BigEndian count
+/// Node is not sending any response for this message
+pub const API_DONE: &str = "_ts_done";
+/// Node accepted control message, data is the same as in request
+pub const API_ACK: &str = "_ts_ack";
+/// Node rejected control message, data is an error message
+pub const API_ERROR: &str = "_ts_error";
+/// Node accepted API connection and ready for receiving control messages
+pub const API_READY: &str = "_ts_ready";
+/// API client enabling (1) or disabling (0) persistent queue for his events, data is 1 byte flag
+/// Disabling queue is removing all events which are not acknowledged yet
+pub const API_QUEUE: &str = "_ts_queue";
+/// Node sending queued event, data is 8 bytes BigEndian entry ID and raw event bytes
+pub const API_QUEUE_EVENT: &str = "_ts_queue_event";
+/// API client acknowledging queued events, data is a list of 8 bytes BigEndian entry IDs
+/// Node is not sending any response for this message
+pub const API_QUEUE_ACK: &str = "_ts_queue_ack";
+/// API client asking for counters of dropped events, Node is responding with the same name
+/// Response data is a list of 4 bytes BigEndian name length, name and 8 bytes BigEndian counter
+pub const API_STATS: &str = "_ts_stats";
@@ -0,0 +1,91 @@
+#![allow(dead_code)]
+extern crate hmac;
+extern crate sha2;
+extern crate rand;
+
+use self::hmac::{Hmac, Mac};
+use self::sha2::Sha256;
+
+use std::fs;
+
+use auth::Authenticator;
+use error::Error;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Allowing only API client tokens listed in a file
+/// File contains one token per line, empty lines and lines starting with "#" are ignored
+/// Tokens are kept as HMAC with random key and compared in constant time,
+/// so that response time wouldn't give a hint about allowed tokens
+pub struct KeyFileAuthenticator {
+    // random key generated on every start
+    hash_key: Vec<u8>,
+    keys: Vec<Vec<u8>>
+}
+
+impl KeyFileAuthenticator {
+    pub fn load(path: &str) -> Result<KeyFileAuthenticator, Error> {
+        let data = match fs::read_to_string(path) {
+            Ok(d) => d,
+            Err(e) => return Err(Error::Io(format!("Unable to read authentication key file {}", path), e))
+        };
+
+        let mut auth = KeyFileAuthenticator {
+            hash_key: (0..32).map(|_| rand::random::<u8>()).collect(),
+            keys: vec![]
+        };
+
+        auth.keys = data.lines()
+                        .map(|line| line.trim())
+                        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                        .filter_map(|line| auth.hash(line).map(|m| m.finalize().into_bytes().to_vec()))
+                        .collect();
+
+        Ok(auth)
+    }
+
+    fn hash(&self, token: &str) -> Option<HmacSha256> {
+        let mut mac = HmacSha256::new_from_slice(&self.hash_key).ok()?;
+        mac.update(token.as_bytes());
+        Some(mac)
+    }
+}
+
+impl Authenticator for KeyFileAuthenticator {
+    fn authenticate(&self, token: &str) -> Result<(), Error> {
+        // checking every key, without stopping at the first match
+        let allowed = match self.hash(token) {
+            Some(mac) => self.keys.iter().fold(false, |found, key| mac.clone().verify_slice(key).is_ok() | found),
+            None => false
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(Error::Auth(String::from("API key is not allowed")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn listed_keys_only() {
+        let path = env::temp_dir().join(format!("treescale-keys-{}", process::id()));
+        fs::write(&path, "# comment\nclient1\n\n  client2  \n").unwrap();
+        let auth = KeyFileAuthenticator::load(path.to_str().unwrap());
+        let _ = fs::remove_file(&path);
+
+        let auth = auth.unwrap();
+        assert!(auth.authenticate("client1").is_ok());
+        assert!(auth.authenticate("client2").is_ok());
+        assert!(auth.authenticate("client").is_err());
+        assert!(auth.authenticate("client12").is_err());
+        assert!(auth.authenticate("# comment").is_err());
+        assert!(auth.authenticate("").is_err());
+    }
+}
@@ -0,0 +1,69 @@
+mod keys;
+mod signed;
+mod webhook;
+mod node;
+mod worker;
+
+pub use self::keys::KeyFileAuthenticator;
+pub use self::signed::SignedTokenAuthenticator;
+pub use self::webhook::WebhookAuthenticator;
+pub use self::node::{NodeAuthenticator, NODE_CHALLENGE_LEN, NODE_RESPONSE_LEN};
+pub use self::worker::AuthWorkers;
+
+use std::sync::Arc;
+use config::AuthConfig;
+use error::Error;
+
+/// Checking API client tokens during handshake, before connection is accepted
+/// Authenticator is shared between TCP handler threads
+pub trait Authenticator: Send + Sync {
+    /// Returns rejection reason if API client with given token is not allowed to connect
+    fn authenticate(&self, token: &str) -> Result<(), Error>;
+
+    /// Blocking authenticators are called from worker threads,
+    /// so that slow checks wouldn't stop other connections of the same TCP handler
+    fn blocking(&self) -> bool {
+        false
+    }
+}
+
+/// Making Authenticator based on given configuration
+/// Returns None if API client authentication is not configured
+pub fn make_authenticator(config: &AuthConfig) -> Result<Option<Arc<dyn Authenticator>>, Error> {
+    let configured = [&config.keys_file, &config.secret, &config.webhook].iter().filter(|v| !v.is_empty()).count();
+    if configured > 1 {
+        return Err(Error::Config(String::from("Only one of key file, secret or webhook could be used for authentication")));
+    }
+
+    if !config.keys_file.is_empty() {
+        return Ok(Some(Arc::new(KeyFileAuthenticator::load(&config.keys_file)?)));
+    }
+
+    if !config.secret.is_empty() {
+        return Ok(Some(Arc::new(SignedTokenAuthenticator::new(config.secret.as_bytes()))));
+    }
+
+    if !config.webhook.is_empty() {
+        return Ok(Some(Arc::new(WebhookAuthenticator::new(&config.webhook, config.webhook_timeout)?)));
+    }
+
+    Ok(None)
+}
+
+/// Making Node authenticator based on given configuration
+/// Returns None if Node authentication is not configured, so every Node is accepted
+pub fn make_node_authenticator(token: &str, config: &AuthConfig) -> Result<Option<Arc<NodeAuthenticator>>, Error> {
+    if !config.node_secret.is_empty() && !config.node_keys.is_empty() {
+        return Err(Error::Config(String::from("Only one of Node secret or Node keys file could be used for Node authentication")));
+    }
+
+    if !config.node_secret.is_empty() {
+        return Ok(Some(Arc::new(NodeAuthenticator::new(token, &config.node_secret))));
+    }
+
+    if !config.node_keys.is_empty() {
+        return Ok(Some(Arc::new(NodeAuthenticator::load(token, &config.node_keys)?)));
+    }
+
+    Ok(None)
+}
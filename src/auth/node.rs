@@ -0,0 +1,178 @@
+#![allow(dead_code)]
+extern crate hmac;
+extern crate sha2;
+extern crate rand;
+
+use self::hmac::{Hmac, Mac};
+use self::sha2::Sha256;
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use error::Error;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Length of the random challenge sent to other Node
+pub const NODE_CHALLENGE_LEN: usize = 16;
+/// Length of the HMAC-SHA256 response for the challenge
+pub const NODE_RESPONSE_LEN: usize = 32;
+
+/// Challenge-response authentication between Nodes
+/// Every Node is sending random challenge, and other side is responding with
+/// HMAC of both challenges with responder and verifier tokens, signed with responder key
+/// Key is a shared cluster secret or Node specific key from keys file
+/// Node which accepted connection is responding only after verifying other side,
+/// so that its response couldn't be relayed for authenticating as it to another Node
+pub struct NodeAuthenticator {
+    // token of current Node
+    token: String,
+    // shared secret for all Nodes in the cluster
+    secret: Vec<u8>,
+    // Node specific keys
+    // Key -> Node Token
+    keys: BTreeMap<String, Vec<u8>>
+}
+
+impl NodeAuthenticator {
+    pub fn new(token: &str, secret: &str) -> NodeAuthenticator {
+        NodeAuthenticator {
+            token: String::from(token),
+            secret: Vec::from(secret),
+            keys: BTreeMap::new()
+        }
+    }
+
+    /// Loading Node keys from file, where each line is "<node token> <key>"
+    /// Empty lines and lines starting with "#" are ignored
+    pub fn load(token: &str, path: &str) -> Result<NodeAuthenticator, Error> {
+        let data = match fs::read_to_string(path) {
+            Ok(d) => d,
+            Err(e) => return Err(Error::Io(format!("Unable to read Node keys file {}", path), e))
+        };
+
+        let mut keys = BTreeMap::new();
+        for line in data.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some(t), Some(k)) => { keys.insert(String::from(t), Vec::from(k)); }
+                _ => return Err(Error::Config(format!("Invalid line in Node keys file {} -> {}", path, line)))
+            }
+        }
+
+        if !keys.contains_key(token) {
+            return Err(Error::Config(format!("Node keys file {} doesn't have key for current Node {}", path, token)));
+        }
+
+        Ok(NodeAuthenticator {
+            token: String::from(token),
+            secret: vec![],
+            keys
+        })
+    }
+
+    /// Making random challenge for other Node
+    pub fn challenge(&self) -> Vec<u8> {
+        (0..NODE_CHALLENGE_LEN).map(|_| rand::random::<u8>()).collect()
+    }
+
+    /// Responding to the challenge from Node with given token
+    /// Our own challenge sent to that Node is mixed into the response
+    pub fn respond(&self, challenge: &[u8], own_challenge: &[u8], peer_token: &str) -> Vec<u8> {
+        let token = self.token.clone();
+        match self.mac(&token, challenge, own_challenge, &token, peer_token) {
+            Some(mac) => mac.finalize().into_bytes().to_vec(),
+            None => vec![0; NODE_RESPONSE_LEN]
+        }
+    }
+
+    /// Checking response of the Node with given token for our challenge
+    /// and for the challenge which it sent to us
+    pub fn verify(&self, challenge: &[u8], peer_challenge: &[u8], peer_token: &str, response: &[u8]) -> bool {
+        // other side could send our challenge back to get response for it from us
+        if peer_token == self.token {
+            return false;
+        }
+
+        match self.mac(peer_token, challenge, peer_challenge, peer_token, &self.token) {
+            Some(mac) => mac.verify_slice(response).is_ok(),
+            None => false
+        }
+    }
+
+    fn mac(&self, key_token: &str, verifier_challenge: &[u8], responder_challenge: &[u8]
+           , responder: &str, verifier: &str) -> Option<HmacSha256> {
+        let key = if self.secret.is_empty() {
+            self.keys.get(key_token)?
+        } else {
+            &self.secret
+        };
+
+        let mut mac = HmacSha256::new_from_slice(key).ok()?;
+        for field in &[verifier_challenge, responder_challenge, responder.as_bytes(), verifier.as_bytes()] {
+            mac.update(&(field.len() as u32).to_be_bytes());
+            mac.update(field);
+        }
+
+        Some(mac)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_response() {
+        let a = NodeAuthenticator::new("A", "secret");
+        let b = NodeAuthenticator::new("B", "secret");
+        let (challenge_a, challenge_b) = (a.challenge(), b.challenge());
+        let response = b.respond(&challenge_a, &challenge_b, "A");
+        assert_eq!(challenge_a.len(), NODE_CHALLENGE_LEN);
+        assert_eq!(response.len(), NODE_RESPONSE_LEN);
+        assert!(a.verify(&challenge_a, &challenge_b, "B", &response));
+        assert!(b.verify(&challenge_b, &challenge_a, "A", &a.respond(&challenge_b, &challenge_a, "B")));
+    }
+
+    #[test]
+    fn tampered_response() {
+        let a = NodeAuthenticator::new("A", "secret");
+        let b = NodeAuthenticator::new("B", "secret");
+        let (challenge_a, challenge_b) = (a.challenge(), b.challenge());
+        let mut response = b.respond(&challenge_a, &challenge_b, "A");
+        response[0] ^= 1;
+        assert!(!a.verify(&challenge_a, &challenge_b, "B", &response));
+
+        // response is bound to both challenges and to the tokens of both Nodes
+        let response = b.respond(&challenge_a, &challenge_b, "A");
+        assert!(!a.verify(&a.challenge(), &challenge_b, "B", &response));
+        assert!(!a.verify(&challenge_a, &b.challenge(), "B", &response));
+        assert!(!a.verify(&challenge_a, &challenge_b, "C", &response));
+    }
+
+    #[test]
+    fn different_secret() {
+        let a = NodeAuthenticator::new("A", "secret");
+        let b = NodeAuthenticator::new("B", "other");
+        let (challenge_a, challenge_b) = (a.challenge(), b.challenge());
+        assert!(!a.verify(&challenge_a, &challenge_b, "B", &b.respond(&challenge_a, &challenge_b, "A")));
+    }
+
+    #[test]
+    fn reflected_challenge() {
+        let a = NodeAuthenticator::new("A", "secret");
+        let (challenge, own) = (a.challenge(), a.challenge());
+        assert!(!a.verify(&challenge, &own, "A", &a.respond(&challenge, &own, "A")));
+    }
+
+    #[test]
+    fn response_is_not_symmetric() {
+        // Node relaying challenges between two connections is getting responses
+        // with verifier and responder challenges in opposite order, so it couldn't reuse them
+        let a = NodeAuthenticator::new("A", "secret");
+        let b = NodeAuthenticator::new("B", "secret");
+        let (challenge_a, challenge_b) = (a.challenge(), b.challenge());
+        let response = b.respond(&challenge_b, &challenge_a, "A");
+        assert!(!a.verify(&challenge_a, &challenge_b, "B", &response));
+    }
+}
@@ -0,0 +1,73 @@
+#![allow(dead_code)]
+extern crate hmac;
+extern crate sha2;
+
+use self::hmac::{Hmac, Mac};
+use self::sha2::Sha256;
+
+use auth::Authenticator;
+use error::Error;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Allowing API client tokens signed with shared secret
+/// Token format is "<client id>.<HEX encoded HMAC-SHA256 of client id>"
+/// so that tokens could be issued without letting Node know about them
+pub struct SignedTokenAuthenticator {
+    secret: Vec<u8>
+}
+
+impl SignedTokenAuthenticator {
+    pub fn new(secret: &[u8]) -> SignedTokenAuthenticator {
+        SignedTokenAuthenticator {
+            secret: Vec::from(secret)
+        }
+    }
+
+    /// Making signed token for given client id
+    pub fn sign(&self, id: &str) -> String {
+        let mut mac = match HmacSha256::new_from_slice(&self.secret) {
+            Ok(m) => m,
+            Err(_) => return String::new()
+        };
+
+        mac.update(id.as_bytes());
+        let signature: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}.{}", id, signature)
+    }
+
+    fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+        if hex.len() % 2 == 1 {
+            return None;
+        }
+
+        (0..(hex.len() / 2)).map(|i| hex.get((i * 2)..(i * 2 + 2)).and_then(|h| u8::from_str_radix(h, 16).ok()))
+                            .collect()
+    }
+}
+
+impl Authenticator for SignedTokenAuthenticator {
+    fn authenticate(&self, token: &str) -> Result<(), Error> {
+        let (id, signature) = match token.rfind('.') {
+            Some(i) => (&token[..i], &token[(i + 1)..]),
+            None => return Err(Error::Auth(String::from("API key is not signed")))
+        };
+
+        let signature = match SignedTokenAuthenticator::decode_hex(signature) {
+            Some(s) => s,
+            None => return Err(Error::Auth(String::from("API key signature is not a valid HEX string")))
+        };
+
+        let mut mac = match HmacSha256::new_from_slice(&self.secret) {
+            Ok(m) => m,
+            Err(_) => return Err(Error::Auth(String::from("Invalid authentication secret")))
+        };
+
+        // comparing in constant time, to not give a hint about valid signature
+        mac.update(id.as_bytes());
+        match mac.verify_slice(&signature) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(Error::Auth(String::from("API key signature is not valid")))
+        }
+    }
+}
@@ -0,0 +1,103 @@
+#![allow(dead_code)]
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs, SocketAddr};
+use std::time::Duration;
+
+use auth::Authenticator;
+use error::Error;
+
+/// Asking external HTTP service to approve API client token
+/// Token is sent as a POST request body, and any 2xx response status means that client is allowed
+/// Request is made from authentication worker thread, so it's not blocking other connections
+pub struct WebhookAuthenticator {
+    // "host:port" for connecting and sending as a Host header
+    host: String,
+    path: String,
+    timeout: Duration
+}
+
+impl WebhookAuthenticator {
+    /// Only plain "http://host[:port][/path]" URLs are supported
+    /// Timeout is in milliseconds
+    pub fn new(url: &str, timeout: u64) -> Result<WebhookAuthenticator, Error> {
+        let rest = match url.find("://") {
+            Some(i) if &url[..i] == "http" => &url[(i + 3)..],
+            Some(_) => return Err(Error::Config(format!("Only http:// authentication webhooks are supported -> {}", url))),
+            None => url
+        };
+
+        let (host, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/")
+        };
+
+        if host.is_empty() {
+            return Err(Error::Config(format!("Authentication webhook URL have no host -> {}", url)));
+        }
+
+        Ok(WebhookAuthenticator {
+            host: if host.contains(':') { String::from(host) } else { format!("{}:80", host) },
+            path: String::from(path),
+            timeout: Duration::from_millis(timeout)
+        })
+    }
+
+    fn request(&self, token: &str) -> Result<u32, Error> {
+        let addresses: Vec<SocketAddr> = match self.host.to_socket_addrs() {
+            Ok(a) => a.collect(),
+            Err(e) => return Err(Error::io("Unable to resolve authentication webhook address", e))
+        };
+
+        let address = match addresses.first() {
+            Some(a) => a,
+            None => return Err(Error::Auth(String::from("Unable to resolve authentication webhook address")))
+        };
+
+        let mut stream = match TcpStream::connect_timeout(address, self.timeout) {
+            Ok(s) => s,
+            Err(e) => return Err(Error::io("Unable to connect to authentication webhook", e))
+        };
+
+        let _ = stream.set_read_timeout(Some(self.timeout));
+        let _ = stream.set_write_timeout(Some(self.timeout));
+
+        let request = format!("POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}"
+                              , self.path, self.host, token.len(), token);
+        if let Err(e) = stream.write_all(request.as_bytes()) {
+            return Err(Error::io("Unable to send authentication webhook request", e));
+        }
+
+        // we only need status line, which is "HTTP/1.1 200 OK"
+        let mut response = vec![];
+        let mut buffer = [0; 512];
+        while !response.contains(&b'\n') {
+            match stream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => response.extend_from_slice(&buffer[..n]),
+                Err(e) => return Err(Error::io("Unable to read authentication webhook response", e))
+            }
+        }
+
+        let response = String::from_utf8_lossy(&response);
+        match response.split_whitespace().nth(1).and_then(|s| s.parse::<u32>().ok()) {
+            Some(status) => Ok(status),
+            None => Err(Error::Auth(String::from("Invalid authentication webhook response")))
+        }
+    }
+}
+
+impl Authenticator for WebhookAuthenticator {
+    fn authenticate(&self, token: &str) -> Result<(), Error> {
+        let status = self.request(token)?;
+        if (200..300).contains(&status) {
+            Ok(())
+        } else {
+            Err(Error::Auth(format!("API key rejected by authentication service with status {}", status)))
+        }
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+}
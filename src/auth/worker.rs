@@ -0,0 +1,60 @@
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Sender, Receiver};
+use std::thread;
+
+use auth::Authenticator;
+use helper::Log;
+use error::Error;
+
+/// Connection ID given by the caller, and API client token for checking
+type AuthJob = (usize, String);
+
+/// Threads for calling blocking Authenticator outside of TCP handler event loop
+/// Result is given to the callback together with connection ID and token from the request
+pub struct AuthWorkers {
+    jobs: Sender<AuthJob>
+}
+
+impl AuthWorkers {
+    /// Starting given count of worker threads, they are stopped when AuthWorkers is dropped
+    pub fn new<F>(authenticator: Arc<dyn Authenticator>, count: usize, done: F) -> AuthWorkers
+        where F: Fn(usize, String, Result<(), Error>) + Send + Sync + 'static {
+        let (jobs, receiver) = mpsc::channel::<AuthJob>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let done = Arc::new(done);
+        for _ in 0..count {
+            let receiver = receiver.clone();
+            let authenticator = authenticator.clone();
+            let done = done.clone();
+            thread::spawn(move || {
+                while let Some((id, token)) = AuthWorkers::next_job(&receiver) {
+                    let result = authenticator.authenticate(&token);
+                    done(id, token, result);
+                }
+            });
+        }
+
+        AuthWorkers { jobs }
+    }
+
+    fn next_job(receiver: &Mutex<Receiver<AuthJob>>) -> Option<AuthJob> {
+        match receiver.lock() {
+            Ok(r) => r.recv().ok(),
+            Err(_) => None
+        }
+    }
+
+    /// Giving API client token to one of the workers
+    /// Returns false if workers are not running anymore
+    pub fn check(&self, id: usize, token: &str) -> bool {
+        match self.jobs.send((id, String::from(token))) {
+            Ok(_) => true,
+            Err(e) => {
+                Log::error("Unable to send API key to authentication worker", e.to_string().as_str());
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+#![allow(dead_code)]
+
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs, Shutdown};
+use std::time::Duration;
+
+use client::ClientConfig;
+use network::{Connection, Capabilities, CAPABILITIES_API_VERSION
+              , CAP_CODECS, CAP_HEARTBEAT, CAP_MAX_FRAME, CAP_EVENT_VERSION, TCP_FRAME_CAPABILITIES};
+use event::{Event, EVENT_VERSION_LEGACY, EVENT_VERSION_HEADER};
+use api::API_ERROR;
+use helper::NetHelper;
+use error::Error;
+
+/// Node handshake and capabilities frames are small, so anything larger is a protocol error
+const CLIENT_MAX_HANDSHAKE_FRAME: usize = 64 * 1024;
+
+/// Blocking TCP connection of API client to Node
+pub struct ClientConnection {
+    stream: TcpStream,
+    // token of the Node which accepted our connection
+    pub node_token: String,
+    // event encoding version which is supported by both sides
+    pub event_version: u8,
+    // max frame size which we are accepting from Node
+    max_frame: usize,
+}
+
+impl ClientConnection {
+    /// Connecting to Node and making handshake as an API client with Value 0
+    /// Capabilities are exchanged if Node is using API version which supports them
+    pub fn connect(config: &ClientConfig) -> Result<ClientConnection, Error> {
+        let address = match config.address.to_socket_addrs() {
+            Ok(mut list) => match list.next() {
+                Some(a) => a,
+                None => return Err(Error::Config(format!("Unable to resolve Node address {}", config.address)))
+            },
+            Err(e) => return Err(Error::Config(format!("Unable to parse Node address {} -> {}", config.address, e)))
+        };
+
+        let timeout = Duration::from_millis(config.connect_timeout);
+        let stream = match TcpStream::connect_timeout(&address, timeout) {
+            Ok(s) => s,
+            Err(e) => return Err(Error::io(format!("Unable to connect to Node {}", config.address).as_str(), e))
+        };
+
+        // handshake should be done in time, after that we are waiting for events as long as needed
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_nodelay(true)?;
+
+        let mut conn = ClientConnection {
+            stream,
+            node_token: String::new(),
+            event_version: EVENT_VERSION_LEGACY,
+            max_frame: config.max_frame,
+        };
+
+        conn.handshake(&config.token)?;
+        conn.stream.set_read_timeout(None)?;
+        Ok(conn)
+    }
+
+    fn handshake(&mut self, token: &str) -> Result<(), Error> {
+        // API version, token length with Value, token and Value 0 for API clients
+        let token_len = token.len();
+        let mut buffer = vec![0; 4 + 4 + token_len + 8];
+        let mut offset = NetHelper::u32_to_bytes(CAPABILITIES_API_VERSION, &mut buffer, 0);
+        offset += NetHelper::u32_to_bytes((token_len + 8) as u32, &mut buffer, offset);
+        buffer[offset..offset + token_len].copy_from_slice(token.as_bytes());
+        offset += token_len;
+        NetHelper::u64_to_bytes(0, &mut buffer, offset);
+        self.write(&buffer)?;
+
+        let (version, _) = Connection::split_api_version(self.read_u32()?);
+        if !Connection::check_api_version(version) {
+            return Err(Error::Handshake(format!("Node responded with invalid API version {}", version)));
+        }
+
+        let data = self.read_frame(CLIENT_MAX_HANDSHAKE_FRAME)?;
+        if data.len() <= 8 {
+            return Err(Error::Handshake(format!("Token and Value field is too short, {} bytes", data.len())));
+        }
+
+        self.node_token = match String::from_utf8(Vec::from(&data[..data.len() - 8])) {
+            Ok(t) => t,
+            Err(e) => return Err(Error::Handshake(format!("Unable to convert received Node Token bytes to string -> {}", e)))
+        };
+
+        // Node with older API version is accepting us right after handshake
+        if version < CAPABILITIES_API_VERSION {
+            return Ok(());
+        }
+
+        loop {
+            let data = self.read_frame(CLIENT_MAX_HANDSHAKE_FRAME)?;
+            // heartbeat frames could come before capabilities
+            if data.len() == 1 {
+                continue;
+            }
+
+            if data[0] == TCP_FRAME_CAPABILITIES {
+                let theirs = Capabilities::from_bytes(&data[1..])?;
+                let ours = self.capabilities();
+                let mut frame = vec![0; 5];
+                NetHelper::u32_to_bytes((ours.len() + 1) as u32, &mut frame, 0);
+                frame[4] = TCP_FRAME_CAPABILITIES;
+                frame.extend_from_slice(&ours);
+                self.write(&frame)?;
+
+                let agreed = Capabilities::from_bytes(&ours)?.intersect(&theirs);
+                self.event_version = agreed.list(CAP_EVENT_VERSION).iter()
+                                        .filter_map(|v| v.parse::<u8>().ok())
+                                        .max().unwrap_or(EVENT_VERSION_LEGACY);
+                return Ok(());
+            }
+
+            // Node is sending error event before closing connection which it's not accepting
+            return match Event::from_raw(&data) {
+                Ok(ref ev) if ev.name == API_ERROR => Err(Error::Auth(String::from_utf8_lossy(&ev.data).into_owned())),
+                _ => Err(Error::Handshake(String::from("Unexpected frame during connection handshake")))
+            };
+        }
+    }
+
+    /// Capabilities of API client, compression is used only between Nodes
+    fn capabilities(&self) -> Vec<u8> {
+        let mut caps = Capabilities::new();
+        caps.set(CAP_CODECS, "binary");
+        caps.set(CAP_HEARTBEAT, "1");
+        caps.set(CAP_MAX_FRAME, self.max_frame.to_string().as_str());
+        caps.set(CAP_EVENT_VERSION, format!("{},{}", EVENT_VERSION_HEADER, EVENT_VERSION_LEGACY).as_str());
+        caps.to_bytes()
+    }
+
+    /// Making another handle for the same socket, so that writing could be done from other thread
+    #[inline(always)]
+    pub fn try_clone(&self) -> Result<TcpStream, Error> {
+        match self.stream.try_clone() {
+            Ok(s) => Ok(s),
+            Err(e) => Err(Error::io("Unable to clone API client connection", e))
+        }
+    }
+
+    #[inline(always)]
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.stream.write_all(data)?;
+        Ok(())
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let mut buffer = vec![0; 4];
+        self.read_exact(&mut buffer)?;
+        NetHelper::bytes_to_u32(&buffer, 0)
+    }
+
+    fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        match self.stream.read_exact(buffer) {
+            Ok(_) => Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(Error::closed()),
+            Err(e) => Err(Error::io("Unable to read from Node connection", e))
+        }
+    }
+
+    /// Reading length prefixed frame, which is an event or 1 byte heartbeat frame
+    pub fn read_frame(&mut self, max_len: usize) -> Result<Vec<u8>, Error> {
+        let len = self.read_u32()? as usize;
+        if len == 0 || len > max_len {
+            return Err(Error::Capacity(format!("Frame size {} is not in allowed range 1..{}", len, max_len)));
+        }
+
+        let mut data = vec![0; len];
+        self.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// Reading next frame from Node, blocking until it's received
+    #[inline(always)]
+    pub fn read(&mut self) -> Result<Vec<u8>, Error> {
+        let max_frame = self.max_frame;
+        self.read_frame(max_frame)
+    }
+
+    #[inline(always)]
+    pub fn close(&self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+    use client::{CLIENT_RECONNECT_MIN, CLIENT_RECONNECT_MAX, CLIENT_MAX_PENDING};
+    use network::LEGACY_API_VERSION;
+
+    /// Token with Value and capabilities frame received by fake Node
+    type Received = (Vec<u8>, Vec<u8>);
+
+    /// Fake Node accepting single API client with given API version
+    /// and sending given frames after the handshake
+    /// Returns client token with Value and capabilities frame sent by client
+    fn fake_node(api_version: u32, frames: Vec<Vec<u8>>) -> (String, thread::JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = vec![0; 8];
+            stream.read_exact(&mut header).unwrap();
+            assert_eq!(NetHelper::bytes_to_u32(&header, 0).unwrap(), CAPABILITIES_API_VERSION);
+            let mut token_value = vec![0; NetHelper::bytes_to_u32(&header, 4).unwrap() as usize];
+            stream.read_exact(&mut token_value).unwrap();
+
+            let mut handshake = vec![0; 8];
+            NetHelper::u32_to_bytes(api_version, &mut handshake, 0);
+            NetHelper::u32_to_bytes(12, &mut handshake, 4);
+            handshake.extend_from_slice(b"node");
+            handshake.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2]);
+            stream.write_all(&handshake).unwrap();
+            for frame in frames {
+                stream.write_all(&frame).unwrap();
+            }
+
+            // rejected client is closing connection without sending capabilities
+            let mut caps = vec![];
+            let mut len = vec![0; 4];
+            if api_version >= CAPABILITIES_API_VERSION && stream.read_exact(&mut len).is_ok() {
+                caps = vec![0; NetHelper::bytes_to_u32(&len, 0).unwrap() as usize];
+                stream.read_exact(&mut caps).unwrap();
+            }
+            (token_value, caps)
+        });
+
+        (address, handle)
+    }
+
+    fn frame(data: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0; 4];
+        NetHelper::u32_to_bytes(data.len() as u32, &mut frame, 0);
+        frame.extend_from_slice(data);
+        frame
+    }
+
+    fn capabilities_frame(caps: &Capabilities) -> Vec<u8> {
+        let mut data = vec![TCP_FRAME_CAPABILITIES];
+        data.extend(caps.to_bytes());
+        frame(&data)
+    }
+
+    fn config(address: &str) -> ClientConfig {
+        ClientConfig {
+            address: String::from(address),
+            token: String::from("client"),
+            connect_timeout: 2000,
+            reply_timeout: 2000,
+            reconnect_min: CLIENT_RECONNECT_MIN,
+            reconnect_max: CLIENT_RECONNECT_MAX,
+            max_pending: CLIENT_MAX_PENDING,
+            max_frame: 1000,
+        }
+    }
+
+    #[test]
+    fn handshake_with_capabilities() {
+        let mut caps = Capabilities::new();
+        caps.set(CAP_EVENT_VERSION, "2,1");
+        caps.set(CAP_HEARTBEAT, "1");
+        // heartbeat frame is skipped before capabilities
+        let (address, node) = fake_node(CAPABILITIES_API_VERSION, vec![frame(&[1]), capabilities_frame(&caps)]);
+
+        let conn = ClientConnection::connect(&config(&address)).unwrap();
+        assert_eq!(conn.node_token, "node");
+        assert_eq!(conn.event_version, EVENT_VERSION_HEADER);
+
+        let (token_value, client_caps) = node.join().unwrap();
+        assert_eq!(token_value, b"client\0\0\0\0\0\0\0\0".to_vec());
+        assert_eq!(client_caps[0], TCP_FRAME_CAPABILITIES);
+        let client_caps = Capabilities::from_bytes(&client_caps[1..]).unwrap();
+        assert_eq!(client_caps.number(CAP_MAX_FRAME), Some(1000));
+        assert!(client_caps.supports(CAP_EVENT_VERSION, "2"));
+    }
+
+    #[test]
+    fn handshake_with_legacy_node() {
+        let (address, node) = fake_node(LEGACY_API_VERSION, vec![]);
+        let conn = ClientConnection::connect(&config(&address)).unwrap();
+        assert_eq!(conn.node_token, "node");
+        assert_eq!(conn.event_version, EVENT_VERSION_LEGACY);
+        node.join().unwrap();
+    }
+
+    #[test]
+    fn handshake_rejected() {
+        let ev = Event {
+            name: String::from(API_ERROR),
+            data: b"Invalid API key".to_vec(),
+            ..Event::default()
+        };
+        let (address, node) = fake_node(CAPABILITIES_API_VERSION, vec![ev.to_raw().unwrap()]);
+
+        match ClientConnection::connect(&config(&address)) {
+            Err(Error::Auth(msg)) => assert_eq!(msg, "Invalid API key"),
+            Err(e) => panic!("Unexpected error -> {}", e),
+            Ok(_) => panic!("Rejected connection shouldn't be made")
+        }
+        assert!(node.join().unwrap().1.is_empty());
+    }
+}
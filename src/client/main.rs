@@ -0,0 +1,647 @@
+#![allow(dead_code)]
+extern crate rand;
+
+use std::io::{self, Write};
+use std::cmp;
+use std::thread;
+use std::net::{TcpStream, Shutdown};
+use std::time::Duration;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{self, Sender, Receiver, RecvTimeoutError};
+use std::collections::VecDeque;
+
+use client::{ClientConnection, SubscriptionType, CLIENT_RECONNECT_MIN, CLIENT_RECONNECT_MAX, CLIENT_MAX_PENDING};
+use event::{Event, Compression};
+use api::{Subscription, SubscriptionMessage, API_EVENT_PREFIX, API_SUBSCRIBE, API_UNSUBSCRIBE
+          , API_DONE, API_ACK, API_ERROR, API_READY};
+use network::{TCP_FRAME_PING, TCP_FRAME_PONG};
+use helper::{Log, NetHelper, NameTrie};
+use error::Error;
+
+/// Options for API client connection
+#[derive(Clone)]
+pub struct ClientConfig {
+    // Node address, like "127.0.0.1:8000"
+    pub address: String,
+    // API client token, events targeted to this token are delivered to us
+    pub token: String,
+    // milliseconds for connecting and making handshake
+    pub connect_timeout: u64,
+    // milliseconds for waiting Node response to subscription requests
+    pub reply_timeout: u64,
+    // milliseconds between reconnect attempts, growing exponentially from min to max
+    pub reconnect_min: u64,
+    pub reconnect_max: u64,
+    // count of events published while reconnecting, after that publishing fails
+    pub max_pending: usize,
+    // max event frame size in bytes which we are accepting from Node
+    pub max_frame: usize,
+}
+
+/// Builder for API client connection options
+pub struct ClientBuilder {
+    config: ClientConfig
+}
+
+/// Node response for subscription requests
+enum Reply {
+    Ack,
+    Error(String),
+    // connection is lost, so request would be sent again after reconnect
+    Disconnected
+}
+
+/// State shared between API client and its reader thread
+struct ClientState {
+    // writing side of current connection, None while reconnecting
+    writer: Option<TcpStream>,
+    event_version: u8,
+    // subscriptions which are restored after reconnect
+    subscriptions: Vec<Subscription>,
+    // raw events published while reconnecting
+    pending: VecDeque<Vec<u8>>,
+    closed: bool,
+}
+
+/// Reading events from Node on a separate thread and reconnecting when connection is lost
+struct ClientReader {
+    config: ClientConfig,
+    state: Arc<Mutex<ClientState>>,
+    events: Sender<Result<Event, Error>>,
+    replies: Sender<Reply>,
+    // responses for subscriptions restored after reconnect, they are not given to the client
+    skip_replies: usize,
+}
+
+/// API client connected to Node with Value 0
+/// Events are received on a separate thread, which is also answering to heartbeat pings
+/// and reconnecting to Node with restored subscriptions when connection is lost
+pub struct Client {
+    config: ClientConfig,
+    state: Arc<Mutex<ClientState>>,
+    events: Receiver<Result<Event, Error>>,
+    replies: Receiver<Reply>,
+    thread: Option<thread::JoinHandle<()>>
+}
+
+/// Blocking iterator over received events, it ends when client is closed
+pub struct Events<'a> {
+    client: &'a Client
+}
+
+impl ClientBuilder {
+    pub fn new(address: &str) -> ClientBuilder {
+        ClientBuilder {
+            config: ClientConfig {
+                address: String::from(address),
+                token: String::new(),
+                connect_timeout: 5000,
+                reply_timeout: 10000,
+                reconnect_min: CLIENT_RECONNECT_MIN,
+                reconnect_max: CLIENT_RECONNECT_MAX,
+                max_pending: CLIENT_MAX_PENDING,
+                max_frame: 16 * 1024 * 1024,
+            }
+        }
+    }
+
+    pub fn token(mut self, token: &str) -> ClientBuilder {
+        self.config.token = String::from(token);
+        self
+    }
+
+    pub fn connect_timeout(mut self, millis: u64) -> ClientBuilder {
+        self.config.connect_timeout = millis;
+        self
+    }
+
+    pub fn reply_timeout(mut self, millis: u64) -> ClientBuilder {
+        self.config.reply_timeout = millis;
+        self
+    }
+
+    /// Min and max milliseconds between reconnect attempts
+    pub fn reconnect(mut self, min: u64, max: u64) -> ClientBuilder {
+        self.config.reconnect_min = min;
+        self.config.reconnect_max = max;
+        self
+    }
+
+    pub fn max_pending(mut self, count: usize) -> ClientBuilder {
+        self.config.max_pending = count;
+        self
+    }
+
+    pub fn max_frame(mut self, size: usize) -> ClientBuilder {
+        self.config.max_frame = size;
+        self
+    }
+
+    /// Connecting to Node and starting reader thread
+    /// Only first connection errors are returned from here, after that client is reconnecting by itself
+    pub fn connect(self) -> Result<Client, Error> {
+        let config = self.config;
+        if config.token.is_empty() {
+            return Err(Error::Config(String::from("API client token couldn't be empty")));
+        }
+
+        if config.reconnect_min == 0 || config.reconnect_min > config.reconnect_max {
+            return Err(Error::Config(String::from("Min reconnect delay should be greater than 0 and not greater than max delay")));
+        }
+
+        if config.connect_timeout == 0 || config.reply_timeout == 0 {
+            return Err(Error::Config(String::from("Connect and reply timeouts should be greater than 0")));
+        }
+
+        let conn = ClientConnection::connect(&config)?;
+        let state = Arc::new(Mutex::new(ClientState {
+            writer: Some(conn.try_clone()?),
+            event_version: conn.event_version,
+            subscriptions: vec![],
+            pending: VecDeque::new(),
+            closed: false,
+        }));
+
+        let (events_s, events_r) = mpsc::channel();
+        let (replies_s, replies_r) = mpsc::channel();
+        let reader = ClientReader {
+            config: config.clone(),
+            state: state.clone(),
+            events: events_s,
+            replies: replies_s,
+            skip_replies: 0,
+        };
+        let thread = thread::spawn(move || reader.run(conn));
+
+        Ok(Client {
+            config,
+            state,
+            events: events_r,
+            replies: replies_r,
+            thread: Some(thread)
+        })
+    }
+}
+
+impl Client {
+    /// Connecting to Node with default options
+    pub fn connect(address: &str, token: &str) -> Result<Client, Error> {
+        ClientBuilder::new(address).token(token).connect()
+    }
+
+    #[inline(always)]
+    pub fn token(&self) -> &str {
+        self.config.token.as_str()
+    }
+
+    /// Checking if client is connected right now, or it's reconnecting
+    pub fn is_connected(&self) -> bool {
+        lock_state(&self.state).writer.is_some()
+    }
+
+    /// Publishing event to the tree, Node is resolving its Path from subscriptions
+    /// If client is reconnecting, event is sent after connection is restored
+    pub fn publish(&self, event: &Event) -> Result<(), Error> {
+        if event.name.starts_with(API_EVENT_PREFIX) {
+            return Err(Error::Protocol(format!("Event names with \"{}\" prefix are reserved for control messages", API_EVENT_PREFIX)));
+        }
+
+        self.send(event)
+    }
+
+    /// Publishing event with given name and data
+    pub fn emit(&self, name: &str, data: &[u8]) -> Result<(), Error> {
+        let ev = Event {
+            name: String::from(name),
+            data: data.to_vec(),
+            ..Event::default()
+        };
+        self.publish(&ev)
+    }
+
+    /// Subscribing to events, channels or tags, event names could have wildcards
+    /// if every Node in the tree is supporting them
+    /// Group subscriptions are made with weight 1
+    pub fn subscribe(&self, kind: SubscriptionType, name: &str) -> Result<(), Error> {
+        let weight = if kind == SubscriptionType::Group { 1 } else { 0 };
+        self.change_subscription(Subscription { kind, name: String::from(name), weight }, true)
+    }
+
+    /// Joining group with given weight, every group event is delivered only to one of its members
+    pub fn subscribe_group(&self, name: &str, weight: u32) -> Result<(), Error> {
+        self.change_subscription(Subscription { kind: SubscriptionType::Group, name: String::from(name), weight }, true)
+    }
+
+    pub fn unsubscribe(&self, kind: SubscriptionType, name: &str) -> Result<(), Error> {
+        // Node is expecting weight for group items, but it's not used for unsubscribing
+        let weight = if kind == SubscriptionType::Group { 1 } else { 0 };
+        self.change_subscription(Subscription { kind, name: String::from(name), weight }, false)
+    }
+
+    /// Telling Node that given count of group events is processed
+    pub fn done(&self, count: u32) -> Result<(), Error> {
+        let mut ev = Event {
+            name: String::from(API_DONE),
+            data: vec![0; 4],
+            ..Event::default()
+        };
+        NetHelper::u32_to_bytes(count, &mut ev.data, 0);
+        self.send(&ev)
+    }
+
+    /// Waiting for the next event from Node
+    /// Error is returned only if client is closed or Node rejected our connection
+    pub fn recv(&self) -> Result<Event, Error> {
+        match self.events.recv() {
+            Ok(r) => r,
+            Err(_) => Err(Error::closed())
+        }
+    }
+
+    /// Waiting for the next event from Node, returns None if there is no event during given timeout
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<Event>, Error> {
+        match self.events.recv_timeout(timeout) {
+            Ok(r) => r.map(Some),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::closed())
+        }
+    }
+
+    #[inline(always)]
+    pub fn iter(&self) -> Events<'_> {
+        Events { client: self }
+    }
+
+    /// Calling given callback for every received event until it returns false
+    pub fn listen<F>(&self, mut callback: F) -> Result<(), Error>
+        where F: FnMut(&Client, Event) -> bool {
+        loop {
+            let ev = self.recv()?;
+            if !callback(self, ev) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Closing connection and stopping reader thread
+    /// Events published while reconnecting are dropped
+    pub fn close(&mut self) {
+        {
+            let mut state = lock_state(&self.state);
+            state.closed = true;
+            state.disconnect();
+        }
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                Log::error("Unable to stop API client", "Reader thread panicked");
+            }
+        }
+    }
+
+    /// Sending event right away, or keeping it until client is reconnected
+    fn send(&self, event: &Event) -> Result<(), Error> {
+        let mut state = lock_state(&self.state);
+        if state.closed {
+            return Err(Error::closed());
+        }
+
+        let raw = match event.to_raw_with(state.event_version, Compression::None) {
+            Some(r) => r,
+            None => return Err(Error::Protocol(String::from("Unable to encode event")))
+        };
+
+        if state.write(&raw) {
+            return Ok(());
+        }
+
+        if state.pending.len() >= self.config.max_pending {
+            return Err(Error::Capacity(format!("Node is not connected and {} events are waiting for reconnect", state.pending.len())));
+        }
+
+        state.pending.push_back(raw);
+        Ok(())
+    }
+
+    fn change_subscription(&self, item: Subscription, subscribe: bool) -> Result<(), Error> {
+        validate_subscription(&item)?;
+        let data = SubscriptionMessage { items: vec![item.clone()] }.to_bytes();
+        // keeping subscription before sending it, so that it would be restored if connection is lost before response
+        {
+            let mut state = lock_state(&self.state);
+            state.subscriptions.retain(|s| s.kind != item.kind || s.name != item.name);
+            if subscribe {
+                state.subscriptions.push(item.clone());
+            }
+        }
+
+        let name = if subscribe { API_SUBSCRIBE } else { API_UNSUBSCRIBE };
+        match self.request(name, data) {
+            Err(Error::Protocol(msg)) => {
+                if subscribe {
+                    lock_state(&self.state).subscriptions.retain(|s| s.kind != item.kind || s.name != item.name);
+                }
+                Err(Error::Protocol(msg))
+            }
+            r => r
+        }
+    }
+
+    /// Sending control message and waiting for Node response
+    /// If connection is lost, request is not failing, because it would be repeated after reconnect
+    fn request(&self, name: &str, data: Vec<u8>) -> Result<(), Error> {
+        // responses for timed out requests are not needed anymore
+        while self.replies.try_recv().is_ok() {}
+
+        {
+            let mut state = lock_state(&self.state);
+            if state.closed {
+                return Err(Error::closed());
+            }
+
+            let ev = Event {
+                name: String::from(name),
+                data,
+                ..Event::default()
+            };
+            let raw = match ev.to_raw_with(state.event_version, Compression::None) {
+                Some(r) => r,
+                None => return Err(Error::Protocol(String::from("Unable to encode control message")))
+            };
+
+            if !state.write(&raw) {
+                return Ok(());
+            }
+        }
+
+        match self.replies.recv_timeout(Duration::from_millis(self.config.reply_timeout)) {
+            Ok(Reply::Ack) | Ok(Reply::Disconnected) => Ok(()),
+            Ok(Reply::Error(msg)) => Err(Error::Protocol(msg)),
+            Err(RecvTimeoutError::Timeout) => Err(Error::io("Node didn't respond to control message"
+                                                            , io::Error::new(io::ErrorKind::TimedOut, name))),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::closed())
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.client.recv().ok()
+    }
+}
+
+impl<'a> IntoIterator for &'a Client {
+    type Item = Event;
+    type IntoIter = Events<'a>;
+
+    fn into_iter(self) -> Events<'a> {
+        self.iter()
+    }
+}
+
+impl ClientState {
+    /// Writing raw data to current connection, connection is dropped if write fails
+    /// Returns false if data is not written
+    fn write(&mut self, data: &[u8]) -> bool {
+        let failed = match self.writer {
+            Some(ref mut w) => w.write_all(data).is_err(),
+            None => return false
+        };
+
+        if failed {
+            self.disconnect();
+        }
+
+        !failed
+    }
+
+    #[inline(always)]
+    fn disconnect(&mut self) {
+        if let Some(w) = self.writer.take() {
+            let _ = w.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl ClientReader {
+    fn run(mut self, conn: ClientConnection) {
+        let mut conn = conn;
+        loop {
+            let e = self.read_events(&mut conn);
+            conn.close();
+            let closed = {
+                let mut state = lock_state(&self.state);
+                state.disconnect();
+                state.closed
+            };
+
+            // client could wait for response, which wouldn't come from this connection
+            let _ = self.replies.send(Reply::Disconnected);
+            if closed {
+                return;
+            }
+
+            Log::warn("Lost connection to Node, reconnecting", format!("{} -> {}", self.config.address, e).as_str());
+            conn = match self.reconnect() {
+                Some(c) => c,
+                None => return
+            };
+            Log::info("Reconnected to Node", conn.node_token.as_str());
+        }
+    }
+
+    /// Reading events until connection is lost
+    fn read_events(&mut self, conn: &mut ClientConnection) -> Error {
+        loop {
+            let data = match conn.read() {
+                Ok(d) => d,
+                Err(e) => return e
+            };
+
+            if data.len() == 1 {
+                if data[0] == TCP_FRAME_PING {
+                    lock_state(&self.state).write(&[0, 0, 0, 1, TCP_FRAME_PONG]);
+                }
+                continue;
+            }
+
+            let ev = match Event::from_raw(&data) {
+                Ok(e) => e,
+                Err(e) => {
+                    Log::warn("Unable to parse event received from Node", e.to_string().as_str());
+                    continue;
+                }
+            };
+
+            if !self.handle_event(ev) {
+                return Error::closed();
+            }
+        }
+    }
+
+    /// Giving event to the client, control messages are handled here
+    /// Returns false if client is not receiving events anymore
+    fn handle_event(&mut self, ev: Event) -> bool {
+        match ev.name.as_str() {
+            API_READY => true,
+            API_ACK | API_ERROR if ev.target == API_SUBSCRIBE || ev.target == API_UNSUBSCRIBE => {
+                let reply = if ev.name == API_ACK {
+                    Reply::Ack
+                } else {
+                    Reply::Error(String::from_utf8_lossy(&ev.data).into_owned())
+                };
+
+                if self.skip_replies > 0 {
+                    self.skip_replies -= 1;
+                    if let Reply::Error(msg) = reply {
+                        Log::warn("Node rejected restored subscriptions", msg.as_str());
+                    }
+                    return true;
+                }
+
+                let _ = self.replies.send(reply);
+                true
+            }
+            // error without request name is sent when Node is not accepting our connection
+            API_ERROR if ev.target.is_empty() => {
+                self.fail(Error::Auth(String::from_utf8_lossy(&ev.data).into_owned()));
+                false
+            }
+            _ => self.events.send(Ok(ev)).is_ok()
+        }
+    }
+
+    /// Stopping client with given error
+    fn fail(&self, e: Error) {
+        lock_state(&self.state).closed = true;
+        let _ = self.events.send(Err(e));
+    }
+
+    /// Connecting to Node again using exponential backoff with random jitter,
+    /// same as Node is reconnecting to its parent
+    /// Returns None if client is closed
+    fn reconnect(&mut self) -> Option<ClientConnection> {
+        let max = self.config.reconnect_max;
+        let mut attempts: u32 = 0;
+        loop {
+            let delay = self.config.reconnect_min.checked_shl(cmp::min(attempts, 63))
+                                                 .map(|d| cmp::min(d, max))
+                                                 .unwrap_or(max);
+            let delay = delay / 2 + rand::random::<u64>() % (delay / 2 + 1);
+            attempts = attempts.saturating_add(1);
+            if !self.sleep(delay) {
+                return None;
+            }
+
+            let mut conn = match ClientConnection::connect(&self.config) {
+                Ok(c) => c,
+                Err(Error::Auth(msg)) => {
+                    Log::error("Node rejected API client connection", msg.as_str());
+                    self.fail(Error::Auth(msg));
+                    return None;
+                }
+                Err(e) => {
+                    Log::warn("Unable to reconnect to Node", e.to_string().as_str());
+                    continue;
+                }
+            };
+
+            match self.attach(&mut conn) {
+                Ok(true) => return Some(conn),
+                Ok(false) => {
+                    conn.close();
+                    return None;
+                }
+                Err(e) => {
+                    conn.close();
+                    Log::warn("Unable to restore API client state after reconnect", e.to_string().as_str());
+                }
+            }
+        }
+    }
+
+    /// Restoring subscriptions and sending events published while reconnecting
+    /// Returns false if client is closed
+    fn attach(&mut self, conn: &mut ClientConnection) -> Result<bool, Error> {
+        let mut state = lock_state(&self.state);
+        if state.closed {
+            return Ok(false);
+        }
+
+        self.skip_replies = 0;
+        if !state.subscriptions.is_empty() {
+            let ev = Event {
+                name: String::from(API_SUBSCRIBE),
+                data: SubscriptionMessage { items: state.subscriptions.clone() }.to_bytes(),
+                ..Event::default()
+            };
+            if let Some(raw) = ev.to_raw_with(conn.event_version, Compression::None) {
+                conn.write(&raw)?;
+                self.skip_replies = 1;
+            }
+        }
+
+        while let Some(raw) = state.pending.pop_front() {
+            if let Err(e) = conn.write(&raw) {
+                state.pending.push_front(raw);
+                return Err(e);
+            }
+        }
+
+        state.writer = Some(conn.try_clone()?);
+        state.event_version = conn.event_version;
+        Ok(true)
+    }
+
+    /// Sleeping given milliseconds, returns false if client is closed meanwhile
+    fn sleep(&self, millis: u64) -> bool {
+        let mut left = millis;
+        loop {
+            if lock_state(&self.state).closed {
+                return false;
+            }
+
+            if left == 0 {
+                return true;
+            }
+
+            let step = cmp::min(left, 100);
+            thread::sleep(Duration::from_millis(step));
+            left -= step;
+        }
+    }
+}
+
+/// Checking subscription locally, so that one invalid item wouldn't fail restoring all of them
+fn validate_subscription(item: &Subscription) -> Result<(), Error> {
+    if item.name.is_empty() {
+        return Err(Error::Protocol(String::from("Subscription name couldn't be empty")));
+    }
+
+    if item.kind == SubscriptionType::Event && !NameTrie::is_valid(&item.name) {
+        return Err(Error::Protocol(String::from("Event name wildcard \">\" could be only the last segment")));
+    }
+
+    if item.kind == SubscriptionType::Group && item.weight == 0 {
+        return Err(Error::Protocol(String::from("Group weight should be greater than 0")));
+    }
+
+    Ok(())
+}
+
+/// Locking shared state, reader thread panic shouldn't make client unusable
+#[inline(always)]
+fn lock_state(state: &Mutex<ClientState>) -> MutexGuard<'_, ClientState> {
+    match state.lock() {
+        Ok(s) => s,
+        Err(e) => e.into_inner()
+    }
+}
@@ -0,0 +1,14 @@
+#![allow(dead_code)]
+mod main;
+mod conn;
+
+pub use self::main::{Client, ClientBuilder, ClientConfig, Events};
+pub use graph::SubscriptionType;
+
+use self::conn::ClientConnection;
+
+/// Default delays in milliseconds between reconnect attempts, growing exponentially from min to max
+pub const CLIENT_RECONNECT_MIN: u64 = 100;
+pub const CLIENT_RECONNECT_MAX: u64 = 10000;
+/// Default count of published events kept while client is reconnecting
+pub const CLIENT_MAX_PENDING: usize = 10000;
@@ -1,14 +1,15 @@
 #![allow(dead_code)]
 extern crate clap;
 
-use helper::Log;
+use api::BalanceStrategy;
+use error::Error;
 
-use self::clap::{Arg, App};
+use self::clap::{Arg, App, ArgMatches};
 
-use std::process;
-use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
 
-pub const APP_VERSION: &'static str = "1.0.34";
+pub const APP_VERSION: &str = "1.0.34";
 pub const MAX_API_VERSION: u32 = 1000;
 
 pub struct NodeConfig {
@@ -16,16 +17,110 @@ pub struct NodeConfig {
     pub token: String,
     pub api_version: u32,
     pub network: NetworkingConfig,
-    pub parent_address: String
+    pub parent_address: String,
+    pub balance: BalanceStrategy,
+    pub queue: QueueConfig,
+    pub auth: AuthConfig
 }
 
+#[derive(Clone)]
 pub struct NetworkingConfig {
     pub tcp_server_host: String,
-    pub concurrency: usize
+    // Unix socket file path for local API clients, empty means Unix socket server is disabled
+    pub unix_socket_path: String,
+    // permissions for Unix socket file, 0 means keeping default ones
+    pub unix_socket_mode: u32,
+    // WebSocket server address for API clients, empty means WebSocket server is disabled
+    pub ws_server_host: String,
+    // comma separated Origin header values allowed for WebSocket clients, empty means any origin is allowed
+    pub ws_origins: String,
+    pub concurrency: usize,
+    // milliseconds to wait before first reconnect attempt to the parent Node
+    pub reconnect_min: u64,
+    // max milliseconds to wait between reconnect attempts
+    pub reconnect_max: u64,
+    // milliseconds of connection inactivity before sending ping, 0 means heartbeat is disabled
+    pub heartbeat_interval: u64,
+    // milliseconds of connection inactivity before closing it as a dead one
+    pub heartbeat_timeout: u64,
+    // max bytes for handshake frames, which are coming before connection is accepted
+    pub max_handshake_frame: usize,
+    // max bytes for event frames
+    pub max_event_frame: usize,
+    // comma separated compression algorithms for event data between Nodes, in order of preference
+    pub compression: String,
+    // event data bigger than this bytes is compressed, 0 means compression is disabled
+    pub compress_threshold: usize,
+    // how many times event could be forwarded between Nodes
+    pub max_hops: u8,
+    // count of recent event IDs kept for dropping duplicates, 0 means duplicates are not checked
+    pub dedup_cache: usize,
+    // PEM files with certificate chain and private key, TLS is enabled if they are set
+    pub tls_cert: String,
+    pub tls_key: String,
+    // PEM file with CA certificates for verifying other Nodes and clients
+    pub tls_ca: String,
+    // requiring certificates from connected clients and Nodes
+    pub tls_client_auth: bool,
+    // name for verifying parent certificate, by default IP address is used
+    pub tls_server_name: String,
+    // connection token should be one of the DNS names in peer certificate
+    pub tls_bind_token: bool
 }
 
-pub fn parse_args() -> NodeConfig {
-    let matches = App::new("TreeScale Node Service")
+pub struct QueueConfig {
+    // directory for persistent queue files, empty path means that queue is disabled
+    pub path: String,
+    // seconds to wait for acknowledgement before sending event again
+    pub redelivery_timeout: u64,
+    // delivery attempts before moving event to dead letter file, 0 means unlimited
+    pub max_attempts: u32,
+    pub dead_letter: bool,
+    // WAL records written between syncing file to disk, 0 means syncing is left to operating system
+    pub sync_every: u32
+}
+
+/// Only one of the API client authentication methods could be configured
+/// and only one of the Node authentication methods
+pub struct AuthConfig {
+    // file with allowed API client tokens
+    pub keys_file: String,
+    // secret for verifying HMAC signed API client tokens
+    pub secret: String,
+    // HTTP URL for asking external service about API client tokens
+    pub webhook: String,
+    // milliseconds to wait for webhook response
+    pub webhook_timeout: u64,
+    // shared secret for authenticating Nodes between each other
+    pub node_secret: String,
+    // file with Node specific keys, as "<node token> <key>" lines
+    pub node_keys: String
+}
+
+/// Parsing command line arguments of current process
+/// Help and version arguments are printing their message and exiting process
+pub fn parse_args() -> Result<NodeConfig, Error> {
+    from_matches(&make_app().get_matches())
+}
+
+/// Parsing given command line arguments, where the first one is a program name
+pub fn parse_args_from<I, T>(args: I) -> Result<NodeConfig, Error>
+    where I: IntoIterator<Item = T>, T: Into<OsString> + Clone {
+    match make_app().get_matches_from_safe(args) {
+        Ok(matches) => from_matches(&matches),
+        Err(e) => Err(Error::Config(e.message))
+    }
+}
+
+impl Default for NodeConfig {
+    /// Configuration with default values of all command line arguments
+    fn default() -> NodeConfig {
+        from_matches(&make_app().get_matches_from(vec!["treescale"])).expect("Default configuration should be valid")
+    }
+}
+
+fn make_app() -> App<'static, 'static> {
+    App::new("TreeScale Node Service")
                     .version(APP_VERSION)
                     .author("TreeScale Inc. <hello@treescale.com>")
                     .about("TreeScale technology endpoint for event distribution and data transfer")
@@ -64,16 +159,183 @@ pub fn parse_args() -> NodeConfig {
                             .value_name("TCP_SERVER_HOST")
                             .help("Starts TCP server listener on give host: default is 0.0.0.0:8000")
                             .takes_value(true))
-        .get_matches();
+                    .arg(Arg::with_name("unix_socket")
+                            .long("unix-socket")
+                            .value_name("SOCKET_PATH")
+                            .help("Starts Unix socket server listener on given file path for local connections")
+                            .takes_value(true))
+                    .arg(Arg::with_name("unix_socket_mode")
+                            .long("unix-socket-mode")
+                            .value_name("OCTAL_MODE")
+                            .help("File permissions for Unix socket, for example 660, by default keeping permissions given by umask")
+                            .takes_value(true))
+                    .arg(Arg::with_name("ws_host")
+                            .long("ws-host")
+                            .value_name("WS_SERVER_HOST")
+                            .help("Starts WebSocket server listener for API clients on given host, for example 0.0.0.0:8080, not allowed with --tls-bind-token")
+                            .takes_value(true))
+                    .arg(Arg::with_name("ws_origins")
+                            .long("ws-origins")
+                            .value_name("ORIGINS")
+                            .help("Comma separated origins allowed to connect to WebSocket server, for example https://example.com. By default any web page opened in a browser could connect, requests without Origin header are always allowed")
+                            .takes_value(true))
+                    .arg(Arg::with_name("reconnect_min")
+                            .long("reconnect-min")
+                            .value_name("MILLISECONDS")
+                            .help("Delay before the first reconnect attempt to the parent Node, default is 500")
+                            .takes_value(true))
+                    .arg(Arg::with_name("reconnect_max")
+                            .long("reconnect-max")
+                            .value_name("MILLISECONDS")
+                            .help("Max delay between reconnect attempts to the parent Node, default is 30000")
+                            .takes_value(true))
+                    .arg(Arg::with_name("heartbeat_interval")
+                            .long("heartbeat-interval")
+                            .value_name("MILLISECONDS")
+                            .help("Sends ping to connections with heartbeat support which are idle for given time, 0 disables heartbeat, default is 5000")
+                            .takes_value(true))
+                    .arg(Arg::with_name("heartbeat_timeout")
+                            .long("heartbeat-timeout")
+                            .value_name("MILLISECONDS")
+                            .help("Closes connections with heartbeat support, or without finished handshake, which are not sending anything for given time, default is 15000")
+                            .takes_value(true))
+                    .arg(Arg::with_name("max_handshake_frame")
+                            .long("max-handshake-frame")
+                            .value_name("BYTES")
+                            .help("Closes connections sending bigger handshake frame, default is 4096")
+                            .takes_value(true))
+                    .arg(Arg::with_name("max_event_frame")
+                            .long("max-event-frame")
+                            .value_name("BYTES")
+                            .help("Closes connections sending bigger event frame, default is 16777216")
+                            .takes_value(true))
+                    .arg(Arg::with_name("compression")
+                            .long("compression")
+                            .value_name("ALGORITHMS")
+                            .help("Comma separated compression algorithms supported for event data between Nodes, default is \"lz4,zstd\"")
+                            .takes_value(true))
+                    .arg(Arg::with_name("compress_threshold")
+                            .long("compress-threshold")
+                            .value_name("BYTES")
+                            .help("Compresses event data bigger than given size, default is 0 which disables compression")
+                            .takes_value(true))
+                    .arg(Arg::with_name("max_hops")
+                            .long("max-hops")
+                            .value_name("COUNT")
+                            .help("Max count of Nodes which could forward an event, default is 32, API version 1 Nodes are starting it again")
+                            .takes_value(true))
+                    .arg(Arg::with_name("dedup_cache")
+                            .long("dedup-cache")
+                            .value_name("COUNT")
+                            .help("Count of recent event IDs kept for dropping duplicate events, default is 4096, 0 disables it")
+                            .takes_value(true))
+                    .arg(Arg::with_name("tls_cert")
+                            .long("tls-cert")
+                            .value_name("PEM_FILE")
+                            .help("Certificate chain for TLS, enables TLS for all TCP connections together with --tls-key")
+                            .takes_value(true))
+                    .arg(Arg::with_name("tls_key")
+                            .long("tls-key")
+                            .value_name("PEM_FILE")
+                            .help("Private key for TLS certificate")
+                            .takes_value(true))
+                    .arg(Arg::with_name("tls_ca")
+                            .long("tls-ca")
+                            .value_name("PEM_FILE")
+                            .help("CA certificates for verifying other Nodes, and clients if --tls-client-auth is set")
+                            .takes_value(true))
+                    .arg(Arg::with_name("tls_client_auth")
+                            .long("tls-client-auth")
+                            .help("Accepts only connections with client certificate signed by --tls-ca (mutual TLS)"))
+                    .arg(Arg::with_name("tls_server_name")
+                            .long("tls-server-name")
+                            .value_name("NAME")
+                            .help("Name for verifying parent Node certificate, default is parent IP address")
+                            .takes_value(true))
+                    .arg(Arg::with_name("tls_bind_token")
+                            .long("tls-bind-token")
+                            .help("Accepts only connections with token listed as a DNS name in their certificate, WebSocket server can't be started with it"))
+                    .arg(Arg::with_name("balance")
+                            .short("b")
+                            .long("balance")
+                            .value_name("STRATEGY")
+                            .help("Strategy for choosing API client from the group members connected to this Node: round-robin, weighted or least-outstanding, default is round-robin. Nodes with group members are always chosen by round-robin")
+                            .takes_value(true))
+                    .arg(Arg::with_name("auth_keys")
+                            .long("auth-keys")
+                            .value_name("FILE")
+                            .help("Accepts only API clients with tokens listed in given file, one token per line")
+                            .takes_value(true))
+                    .arg(Arg::with_name("auth_secret")
+                            .long("auth-secret")
+                            .value_name("SECRET")
+                            .env("TREESCALE_AUTH_SECRET")
+                            .hide_env_values(true)
+                            .help("Accepts only API clients with tokens signed by given secret, as <id>.<hex hmac-sha256 of id>")
+                            .takes_value(true))
+                    .arg(Arg::with_name("auth_secret_file")
+                            .long("auth-secret-file")
+                            .value_name("FILE")
+                            .help("Reads API client token secret from given file, so that it wouldn't be visible in process list")
+                            .takes_value(true))
+                    .arg(Arg::with_name("auth_webhook")
+                            .long("auth-webhook")
+                            .value_name("URL")
+                            .help("Accepts only API clients approved by given HTTP service, token is sent as a POST body")
+                            .takes_value(true))
+                    .arg(Arg::with_name("auth_webhook_timeout")
+                            .long("auth-webhook-timeout")
+                            .value_name("MILLISECONDS")
+                            .help("Time to wait for authentication webhook response, default is 2000")
+                            .takes_value(true))
+                    .arg(Arg::with_name("node_secret")
+                            .long("node-secret")
+                            .value_name("SECRET")
+                            .env("TREESCALE_NODE_SECRET")
+                            .hide_env_values(true)
+                            .help("Shared cluster secret for Node authentication, Nodes without it are accepted only as API clients")
+                            .takes_value(true))
+                    .arg(Arg::with_name("node_secret_file")
+                            .long("node-secret-file")
+                            .value_name("FILE")
+                            .help("Reads shared cluster secret for Node authentication from given file, so that it wouldn't be visible in process list")
+                            .takes_value(true))
+                    .arg(Arg::with_name("node_keys")
+                            .long("node-keys")
+                            .value_name("FILE")
+                            .help("File with Node specific keys for Node authentication, one \"<node token> <key>\" per line")
+                            .takes_value(true))
+                    .arg(Arg::with_name("queue_dir")
+                            .long("queue-dir")
+                            .value_name("DIRECTORY")
+                            .help("Directory for persistent queue of durable API clients, queue is disabled if not set")
+                            .takes_value(true))
+                    .arg(Arg::with_name("queue_redelivery")
+                            .long("queue-redelivery")
+                            .value_name("SECONDS")
+                            .help("Seconds to wait for event acknowledgement before sending it again, default is 30")
+                            .takes_value(true))
+                    .arg(Arg::with_name("queue_attempts")
+                            .long("queue-attempts")
+                            .value_name("COUNT")
+                            .help("Delivery attempts before moving event to dead letter file, 0 means unlimited, default is 5")
+                            .takes_value(true))
+                    .arg(Arg::with_name("queue_sync")
+                            .long("queue-sync")
+                            .value_name("COUNT")
+                            .help("WAL records written before syncing queue file to disk, 0 leaves it to operating system, default is 1")
+                            .takes_value(true))
+                    .arg(Arg::with_name("queue_no_dead_letter")
+                            .long("queue-no-dead-letter")
+                            .help("Dropping events which reached delivery attempts limit, instead of keeping them in dead letter file"))
+}
 
-    NodeConfig {
+fn from_matches(matches: &ArgMatches) -> Result<NodeConfig, Error> {
+    Ok(NodeConfig {
         value: match matches.value_of("value") {
             Some(v) => match String::from(v).parse::<u64>() {
                 Ok(vv) => vv,
-                Err(e) => {
-                    Log::error("Unable to parse given Node Value", e.description());
-                    process::exit(1);
-                }
+                Err(e) => return Err(Error::Config(format!("Unable to parse given Node Value -> {}", e)))
             },
             None => 0
         },
@@ -86,12 +348,10 @@ pub fn parse_args() -> NodeConfig {
         api_version: match matches.value_of("api") {
             Some(v) => match String::from(v).parse::<u32>() {
                 Ok(vv) => vv,
-                Err(e) => {
-                    Log::error("Unable to parse given API Version", e.description());
-                    process::exit(1);
-                }
+                Err(e) => return Err(Error::Config(format!("Unable to parse given API Version -> {}", e)))
             },
-            None => 1
+            // Node is using the latest API version by default
+            None => 0
         },
 
         network: NetworkingConfig {
@@ -99,21 +359,198 @@ pub fn parse_args() -> NodeConfig {
                 Some(v) => String::from(v),
                 None => String::from("0.0.0.0:8000")
             },
+            unix_socket_path: match matches.value_of("unix_socket") {
+                Some(v) => String::from(v),
+                None => String::new()
+            },
+            unix_socket_mode: match matches.value_of("unix_socket_mode") {
+                Some(v) => match u32::from_str_radix(v, 8) {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Unix socket mode parameter -> {}", e)))
+                },
+                None => 0
+            },
+            ws_server_host: match matches.value_of("ws_host") {
+                Some(v) => String::from(v),
+                None => String::new()
+            },
+            ws_origins: match matches.value_of("ws_origins") {
+                Some(v) => String::from(v),
+                None => String::new()
+            },
             concurrency: match matches.value_of("concurrency") {
                 Some(v) => match String::from(v).parse::<usize>() {
                     Ok(vv) => vv,
-                    Err(e) => {
-                        Log::error("Unable to parse given Concurrency Level parameter", e.description());
-                        process::exit(1);
-                    }
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Concurrency Level parameter -> {}", e)))
                 },
                 None => 0
             },
+            reconnect_min: match matches.value_of("reconnect_min") {
+                Some(v) => match String::from(v).parse::<u64>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Reconnect Min delay -> {}", e)))
+                },
+                None => 500
+            },
+            reconnect_max: match matches.value_of("reconnect_max") {
+                Some(v) => match String::from(v).parse::<u64>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Reconnect Max delay -> {}", e)))
+                },
+                None => 30000
+            },
+            heartbeat_interval: match matches.value_of("heartbeat_interval") {
+                Some(v) => match String::from(v).parse::<u64>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Heartbeat Interval -> {}", e)))
+                },
+                None => 5000
+            },
+            heartbeat_timeout: match matches.value_of("heartbeat_timeout") {
+                Some(v) => match String::from(v).parse::<u64>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Heartbeat Timeout -> {}", e)))
+                },
+                None => 15000
+            },
+            max_handshake_frame: match matches.value_of("max_handshake_frame") {
+                Some(v) => match String::from(v).parse::<usize>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Max Handshake Frame size -> {}", e)))
+                },
+                None => 4096
+            },
+            max_event_frame: match matches.value_of("max_event_frame") {
+                Some(v) => match String::from(v).parse::<usize>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Max Event Frame size -> {}", e)))
+                },
+                None => 16 * 1024 * 1024
+            },
+            compression: match matches.value_of("compression") {
+                Some(v) => String::from(v),
+                None => String::from("lz4,zstd")
+            },
+            compress_threshold: match matches.value_of("compress_threshold") {
+                Some(v) => match String::from(v).parse::<usize>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Compress Threshold size -> {}", e)))
+                },
+                None => 0
+            },
+            max_hops: match matches.value_of("max_hops") {
+                Some(v) => match String::from(v).parse::<u8>() {
+                    Ok(vv) if vv > 0 => vv,
+                    Ok(_) => return Err(Error::Config(String::from("Max Hops count should be greater than 0"))),
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Max Hops count -> {}", e)))
+                },
+                None => 32
+            },
+            dedup_cache: match matches.value_of("dedup_cache") {
+                Some(v) => match String::from(v).parse::<usize>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Dedup Cache size -> {}", e)))
+                },
+                None => 4096
+            },
+            tls_cert: match matches.value_of("tls_cert") {
+                Some(v) => String::from(v),
+                None => String::new()
+            },
+            tls_key: match matches.value_of("tls_key") {
+                Some(v) => String::from(v),
+                None => String::new()
+            },
+            tls_ca: match matches.value_of("tls_ca") {
+                Some(v) => String::from(v),
+                None => String::new()
+            },
+            tls_client_auth: matches.is_present("tls_client_auth"),
+            tls_server_name: match matches.value_of("tls_server_name") {
+                Some(v) => String::from(v),
+                None => String::new()
+            },
+            tls_bind_token: matches.is_present("tls_bind_token"),
         },
 
         parent_address: match matches.value_of("parent") {
             Some(v) => String::from(v),
             None => String::new()
         },
+
+        balance: match matches.value_of("balance") {
+            Some(v) => match BalanceStrategy::from_name(v) {
+                Some(b) => b,
+                None => return Err(Error::Config(format!("Unable to parse given Balance Strategy -> {}", v)))
+            },
+            None => BalanceStrategy::RoundRobin
+        },
+
+        queue: QueueConfig {
+            path: match matches.value_of("queue_dir") {
+                Some(v) => String::from(v),
+                None => String::new()
+            },
+            redelivery_timeout: match matches.value_of("queue_redelivery") {
+                Some(v) => match String::from(v).parse::<u64>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Queue Redelivery timeout -> {}", e)))
+                },
+                None => 30
+            },
+            max_attempts: match matches.value_of("queue_attempts") {
+                Some(v) => match String::from(v).parse::<u32>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Queue Attempts count -> {}", e)))
+                },
+                None => 5
+            },
+            dead_letter: !matches.is_present("queue_no_dead_letter"),
+            sync_every: match matches.value_of("queue_sync") {
+                Some(v) => match String::from(v).parse::<u32>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Queue Sync count -> {}", e)))
+                },
+                None => 1
+            },
+        },
+
+        auth: AuthConfig {
+            keys_file: match matches.value_of("auth_keys") {
+                Some(v) => String::from(v),
+                None => String::new()
+            },
+            secret: read_secret(matches, "auth_secret", "auth_secret_file")?,
+            webhook: match matches.value_of("auth_webhook") {
+                Some(v) => String::from(v),
+                None => String::new()
+            },
+            webhook_timeout: match matches.value_of("auth_webhook_timeout") {
+                Some(v) => match String::from(v).parse::<u64>() {
+                    Ok(vv) => vv,
+                    Err(e) => return Err(Error::Config(format!("Unable to parse given Authentication Webhook timeout -> {}", e)))
+                },
+                None => 2000
+            },
+            node_secret: read_secret(matches, "node_secret", "node_secret_file")?,
+            node_keys: match matches.value_of("node_keys") {
+                Some(v) => String::from(v),
+                None => String::new()
+            },
+        },
+    })
+}
+
+/// Getting secret from command line, environment variable or file
+/// Trailing new line is removed from the file content
+fn read_secret(matches: &ArgMatches, name: &str, file_name: &str) -> Result<String, Error> {
+    match (matches.value_of(name), matches.value_of(file_name)) {
+        (Some(_), Some(_)) => Err(Error::Config(format!("Only one of --{} or --{} could be given", name.replace('_', "-"), file_name.replace('_', "-")))),
+        (Some(v), None) => Ok(String::from(v)),
+        (None, Some(path)) => match fs::read_to_string(path) {
+            Ok(v) => Ok(String::from(v.trim_end_matches(['\r', '\n']))),
+            Err(e) => Err(Error::Io(format!("Unable to read secret file {}", path), e))
+        },
+        (None, None) => Ok(String::new())
     }
-}
\ No newline at end of file
+}
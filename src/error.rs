@@ -0,0 +1,71 @@
+#![allow(dead_code)]
+
+use std::fmt;
+use std::io;
+use std::error;
+
+/// Errors of Node services, connections and parsed data
+/// Kind of the error is telling what went wrong, and message is giving details about it
+#[derive(Debug)]
+pub enum Error {
+    /// Socket, file or POLL service operation failed
+    Io(String, io::Error),
+    /// Received frame or event data is not valid
+    Protocol(String),
+    /// Connection handshake or capabilities exchange failed
+    Handshake(String),
+    /// Given configuration is not valid
+    Config(String),
+    /// API client or Node authentication failed
+    Auth(String),
+    /// Data is larger than allowed, or there is no space for it
+    Capacity(String)
+}
+
+impl Error {
+    /// Making I/O error with a message about failed operation
+    #[inline(always)]
+    pub fn io(msg: &str, e: io::Error) -> Error {
+        Error::Io(String::from(msg), e)
+    }
+
+    /// Making I/O error for connection closed by other side
+    #[inline(always)]
+    pub fn closed() -> Error {
+        Error::io("Connection closed", io::Error::new(io::ErrorKind::UnexpectedEof, "end of stream"))
+    }
+
+    /// Checking if this error is just a closed connection, not a failure
+    pub fn is_closed(&self) -> bool {
+        match *self {
+            Error::Io(_, ref e) => e.kind() == io::ErrorKind::UnexpectedEof,
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref msg, ref e) if msg.is_empty() => write!(f, "{}", e),
+            Error::Io(ref msg, ref e) => write!(f, "{} -> {}", msg, e),
+            Error::Protocol(ref msg) | Error::Handshake(ref msg) | Error::Config(ref msg)
+            | Error::Auth(ref msg) | Error::Capacity(ref msg) => write!(f, "{}", msg)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(_, ref e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(String::new(), e)
+    }
+}
@@ -0,0 +1,190 @@
+#![allow(dead_code)]
+extern crate serde_json;
+extern crate base64;
+
+use self::serde_json::{Value, Map};
+use self::base64::Engine;
+use self::base64::engine::general_purpose::STANDARD as BASE64;
+
+use event::Event;
+use helper::{Path, NetHelper};
+use error::Error;
+
+use std::sync::Arc;
+
+pub const EVENT_CODEC_BINARY: u8 = 0;
+pub const EVENT_CODEC_JSON: u8 = 1;
+
+/// Encoding used by API client for events
+/// Nodes are always using binary encoding between each other
+/// and events are converted only when they are read from or written to API client
+#[derive(Clone, Copy, PartialEq)]
+pub enum EventCodec {
+    Binary,
+    Json
+}
+
+impl EventCodec {
+    #[inline(always)]
+    pub fn from_id(id: u8) -> Option<EventCodec> {
+        match id {
+            EVENT_CODEC_BINARY => Some(EventCodec::Binary),
+            EVENT_CODEC_JSON => Some(EventCodec::Json),
+            _ => None
+        }
+    }
+
+    #[inline(always)]
+    pub fn id(&self) -> u8 {
+        match *self {
+            EventCodec::Binary => EVENT_CODEC_BINARY,
+            EventCodec::Json => EVENT_CODEC_JSON
+        }
+    }
+
+    /// Parsing event from frame data, without length prefix
+    pub fn decode(&self, data: &[u8]) -> Result<Event, Error> {
+        match *self {
+            EventCodec::Binary => Event::from_raw(data),
+            EventCodec::Json => match String::from_utf8(data.to_vec()) {
+                Ok(text) => EventCodec::from_json(&text),
+                Err(_) => Err(Error::Protocol(String::from("JSON event is not valid UTF-8")))
+            }
+        }
+    }
+
+    /// Converting binary length prefixed event to the frame of this codec
+    /// Control frames are only 1 byte long, so they are kept as is
+    pub fn encode_raw(&self, raw: Arc<Vec<u8>>) -> Result<Arc<Vec<u8>>, Error> {
+        if *self == EventCodec::Binary || raw.len() <= 5 {
+            return Ok(raw);
+        }
+
+        let event = Event::from_raw(&raw[4..])?;
+        let text = EventCodec::to_json(&event);
+        let mut frame = vec![0; 4 + text.len()];
+        NetHelper::u32_to_bytes(text.len() as u32, &mut frame, 0);
+        frame[4..].copy_from_slice(text.as_bytes());
+        Ok(Arc::new(frame))
+    }
+
+    /// Converting Event into JSON object
+    /// Path is given as a hex string of its bytes, and empty for zero path
+    /// Data is given as a "data" string if it's valid UTF-8, otherwise as "data_base64"
+    /// Event header is given only if event has it, with ID as a hex string
+    pub fn to_json(event: &Event) -> String {
+        let mut obj = Map::new();
+        obj.insert(String::from("name"), Value::String(event.name.clone()));
+        obj.insert(String::from("from"), Value::String(event.from.clone()));
+        obj.insert(String::from("target"), Value::String(event.target.clone()));
+        let path = match event.path.to_bytes() {
+            Some(bytes) => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+            None => String::new()
+        };
+        obj.insert(String::from("path"), Value::String(path));
+        match String::from_utf8(event.data.clone()) {
+            Ok(s) => obj.insert(String::from("data"), Value::String(s)),
+            Err(_) => obj.insert(String::from("data_base64"), Value::String(BASE64.encode(&event.data)))
+        };
+
+        if event.id != 0 {
+            obj.insert(String::from("id"), Value::String(format!("{:016x}", event.id)));
+            obj.insert(String::from("timestamp"), Value::from(event.timestamp));
+            obj.insert(String::from("hops"), Value::from(event.hops));
+            if let Some(ttl) = event.ttl {
+                obj.insert(String::from("ttl"), Value::from(ttl));
+            }
+            let headers = event.headers.iter().map(|(k, v)| (k.clone(), Value::String(v.clone()))).collect();
+            obj.insert(String::from("headers"), Value::Object(headers));
+        }
+
+        Value::Object(obj).to_string()
+    }
+
+    /// Parsing Event from JSON object
+    /// "data" could be a string or any other JSON value, which is kept as a JSON text
+    /// if "path" is empty, Node would calculate it by event name or target
+    /// optional "id" is a hex string, "ttl" is a hop limit and "headers" is an object with string values
+    pub fn from_json(text: &str) -> Result<Event, Error> {
+        let value: Value = match serde_json::from_str(text) {
+            Ok(v) => v,
+            Err(e) => return Err(Error::Protocol(format!("Invalid JSON event -> {}", e)))
+        };
+
+        let obj = match value {
+            Value::Object(o) => o,
+            _ => return Err(Error::Protocol(String::from("JSON event should be an object")))
+        };
+
+        let field = |name: &str| -> Result<String, Error> {
+            match obj.get(name) {
+                Some(Value::String(s)) => Ok(s.clone()),
+                Some(Value::Null) | None => Ok(String::new()),
+                Some(_) => Err(Error::Protocol(format!("JSON event field \"{}\" should be a string", name)))
+            }
+        };
+
+        let mut event = Event {
+            name: field("name")?,
+            from: field("from")?,
+            target: field("target")?,
+            path: EventCodec::path_from_hex(&field("path")?)?,
+            ..Event::default()
+        };
+        event.data = match (obj.get("data"), obj.get("data_base64")) {
+            (_, Some(Value::String(s))) => match BASE64.decode(s) {
+                Ok(d) => d,
+                Err(e) => return Err(Error::Protocol(format!("Invalid base64 in JSON event data -> {}", e)))
+            },
+            (Some(Value::String(s)), _) => s.clone().into_bytes(),
+            (Some(Value::Null), _) | (None, _) => vec![],
+            (Some(v), _) => v.to_string().into_bytes()
+        };
+
+        let id = field("id")?;
+        if !id.is_empty() {
+            event.id = match u64::from_str_radix(&id, 16) {
+                Ok(id) => id,
+                Err(_) => return Err(Error::Protocol(String::from("JSON event id should be a hex string")))
+            };
+        }
+
+        match obj.get("ttl") {
+            Some(Value::Number(n)) => match n.as_u64() {
+                Some(ttl) if ttl <= u64::from(u8::MAX) => event.ttl = Some(ttl as u8),
+                _ => return Err(Error::Protocol(String::from("JSON event ttl should be a number from 0 to 255")))
+            },
+            Some(Value::Null) | None => {},
+            Some(_) => return Err(Error::Protocol(String::from("JSON event ttl should be a number")))
+        }
+
+        match obj.get("headers") {
+            Some(Value::Object(headers)) => for (key, value) in headers {
+                match value {
+                    Value::String(s) => event.headers.insert(key.clone(), s.clone()),
+                    _ => return Err(Error::Protocol(format!("JSON event header \"{}\" should be a string", key)))
+                };
+            },
+            Some(Value::Null) | None => {},
+            Some(_) => return Err(Error::Protocol(String::from("JSON event headers should be an object")))
+        }
+
+        Ok(event)
+    }
+
+    fn path_from_hex(hex: &str) -> Result<Path, Error> {
+        if hex.len() % 2 == 1 {
+            return Err(Error::Protocol(String::from("JSON event path should be a hex string")));
+        }
+
+        let mut bytes = Vec::with_capacity(hex.len() / 2);
+        for i in 0..hex.len() / 2 {
+            match hex.get(i * 2..i * 2 + 2).map(|h| u8::from_str_radix(h, 16)) {
+                Some(Ok(b)) => bytes.push(b),
+                _ => return Err(Error::Protocol(String::from("JSON event path should be a hex string")))
+            }
+        }
+
+        Path::from_bytes(&bytes)
+    }
+}
@@ -0,0 +1,100 @@
+#![allow(dead_code)]
+extern crate lz4_flex;
+extern crate zstd;
+
+use error::Error;
+
+/// Event flags for compressed event data, kept in the event header
+pub const EVENT_FLAG_LZ4: u8 = 0x01;
+pub const EVENT_FLAG_ZSTD: u8 = 0x02;
+const EVENT_FLAG_COMPRESSION: u8 = EVENT_FLAG_LZ4 | EVENT_FLAG_ZSTD;
+
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compression algorithm for event data
+/// It's used only between Nodes, which have negotiated it during handshake
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compression {
+    None,
+    Lz4,
+    Zstd
+}
+
+impl Compression {
+    #[inline(always)]
+    pub fn from_name(name: &str) -> Option<Compression> {
+        match name {
+            "lz4" => Some(Compression::Lz4),
+            "zstd" => Some(Compression::Zstd),
+            _ => None
+        }
+    }
+
+    #[inline(always)]
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Compression::None => "",
+            Compression::Lz4 => "lz4",
+            Compression::Zstd => "zstd"
+        }
+    }
+
+    /// Getting compression from event flags
+    #[inline(always)]
+    pub fn from_flags(flags: u8) -> Compression {
+        match flags & EVENT_FLAG_COMPRESSION {
+            EVENT_FLAG_LZ4 => Compression::Lz4,
+            EVENT_FLAG_ZSTD => Compression::Zstd,
+            _ => Compression::None
+        }
+    }
+
+    /// Replacing compression flags with this compression
+    #[inline(always)]
+    pub fn to_flags(self, flags: u8) -> u8 {
+        let flags = flags & !EVENT_FLAG_COMPRESSION;
+        match self {
+            Compression::None => flags,
+            Compression::Lz4 => flags | EVENT_FLAG_LZ4,
+            Compression::Zstd => flags | EVENT_FLAG_ZSTD
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match *self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+            Compression::Zstd => match zstd::bulk::compress(data, ZSTD_LEVEL) {
+                Ok(d) => Ok(d),
+                Err(e) => Err(Error::io("Unable to compress event data with zstd", e))
+            }
+        }
+    }
+
+    /// Decompressing event data, which shouldn't be larger than given max length
+    pub fn decompress(&self, data: &[u8], max_len: usize) -> Result<Vec<u8>, Error> {
+        match *self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Lz4 => {
+                // first 4 bytes are little endian uncompressed size
+                if data.len() < 4 {
+                    return Err(Error::Protocol(String::from("LZ4 compressed event data is too short")));
+                }
+
+                let size = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+                if size > max_len {
+                    return Err(Error::Capacity(format!("Compressed event is larger than max frame size, {} bytes", size)));
+                }
+
+                match lz4_flex::decompress_size_prepended(data) {
+                    Ok(d) => Ok(d),
+                    Err(e) => Err(Error::Protocol(format!("Unable to decompress event data with lz4 -> {}", e)))
+                }
+            }
+            Compression::Zstd => match zstd::bulk::decompress(data, max_len) {
+                Ok(d) => Ok(d),
+                Err(e) => Err(Error::Protocol(format!("Unable to decompress event data with zstd -> {}", e)))
+            }
+        }
+    }
+}
@@ -5,17 +5,18 @@ use self::mio::{PollOpt, Ready};
 
 use node::{Node, EVENT_RECEIVER_CHANNEL_TOKEN};
 use event::Event;
-use helper::Log;
+use error::Error;
 
-use std::error::Error;
-use std::process;
-
-pub type EventCallback = Box<Fn(&Event, &mut Node) -> bool>;
+/// Callback for local event handling inside Node process
+/// Returning false is stopping other callbacks of the same event
+pub type EventCallback = Box<dyn Fn(&Event, &mut Node) -> bool>;
 
 
 pub enum EventCMD {
     None,
-    HandleEvent
+    HandleEvent,
+    // stopping Node event loop from other thread
+    Stop
 }
 
 pub struct EventCommand {
@@ -35,19 +36,27 @@ impl EventCommand {
     }
 }
 
+impl Default for EventCommand {
+    fn default() -> EventCommand {
+        EventCommand::new()
+    }
+}
+
 
 pub trait EventHandler {
     /// Init event handler
-    fn init_event(&mut self);
+    fn init_event(&mut self) -> Result<(), Error>;
     /// Adding new callback to event
     /// or adding an event with given name if it's not exists
+    /// Name could have wildcards, like "build.*.output" or "docker.>"
     fn on(&mut self, name: &str, callback: EventCallback);
 
     /// Removing event from callbacks list
     fn rm(&mut self, name: &str);
 
-    /// Run callbacks for specific event name
+    /// Run callbacks for all names matching to event name
     /// Using given Event object for callback argument
+    /// Callback returning false is stopping only the callbacks registered with the same name
     fn trigger(&mut self, event: &Event);
 
     /// Function to trigger events from local functions
@@ -58,24 +67,21 @@ pub trait EventHandler {
 }
 
 impl EventHandler for Node {
-    fn init_event(&mut self) {
+    fn init_event(&mut self) -> Result<(), Error> {
         // Registering Networking receiver
         match self.poll.register(&self.event_receiver_chan
                                  , EVENT_RECEIVER_CHANNEL_TOKEN
                                  , Ready::readable()
                                  , PollOpt::edge()) {
-            Ok(_) => {},
-            Err(e) => {
-                Log::error("Unable to register Event Handler receiver channel to Node POLL service"
-                           , e.description());
-                process::exit(1);
-            }
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::io("Unable to register Event Handler receiver channel to Node POLL service", e))
         }
     }
 
     #[inline(always)]
     fn on(&mut self, name: &str, callback: EventCallback) {
         let name_str = String::from(name);
+        self.callback_patterns.insert(name);
         let cbs = match self.callbacks.remove(&name_str) {
             Some(mut callbacks) => {
                 callbacks.push(callback);
@@ -91,31 +97,39 @@ impl EventHandler for Node {
     #[inline(always)]
     fn rm(&mut self, name: &str) {
         self.callbacks.remove(&String::from(name));
+        self.callback_patterns.remove(name);
     }
 
     #[inline(always)]
     fn trigger(&mut self, event: &Event) {
-        if !self.callbacks.contains_key(&event.name) {
-            return;
-        }
-
-        let callbacks = self.callbacks.remove(&event.name).unwrap();
-        for cb in &callbacks {
-            // if callback returning false then breaking the loop
-            if !cb(event, self) {
-                break;
+        let names: Vec<String> = self.callback_patterns.matches(&event.name)
+                                                       .into_iter().map(String::from).collect();
+        for name in names {
+            let callbacks = match self.callbacks.remove(&name) {
+                Some(cbs) => cbs,
+                None => continue
+            };
+
+            // if callback returning false then skipping remaining callbacks of this name only,
+            // other matching names are still getting the event
+            for cb in &callbacks {
+                if !cb(event, self) {
+                    break;
+                }
             }
-        }
 
-        self.callbacks.insert(event.name.clone(), callbacks);
+            self.callbacks.insert(name, callbacks);
+        }
     }
 
     #[inline(always)]
     fn trigger_local(&mut self, name: &str, from: String, data: Vec<u8>) {
-        let mut ev = Event::default();
-        ev.from = from;
-        ev.name = String::from(name);
-        ev.data = data;
+        let ev = Event {
+            from,
+            name: String::from(name),
+            data,
+            ..Event::default()
+        };
 
         self.trigger(&ev);
     }
@@ -123,27 +137,18 @@ impl EventHandler for Node {
     #[inline(always)]
     fn event_notify(&mut self) {
         // trying to get commands while there is available data
-        loop {
-            match self.event_receiver_chan.try_recv() {
-                Ok(cmd) => {
-                    let mut command: EventCommand = cmd;
-                    match command.cmd {
-                        EventCMD::HandleEvent => {
-                            while !command.event.is_empty() {
-                                // triggering given event
-                                self.trigger(&command.event.remove(0));
-                            }
-                        }
-                        EventCMD::None => {}
+        // if we got error, then data is unavailable
+        // and breaking receive loop
+        while let Ok(mut command) = self.event_receiver_chan.try_recv() {
+            match command.cmd {
+                EventCMD::HandleEvent => {
+                    while !command.event.is_empty() {
+                        // triggering given event
+                        self.trigger(&command.event.remove(0));
                     }
                 }
-                // if we got error, then data is unavailable
-                // and breaking receive loop
-                Err(e) => {
-                    Log::warn("EventHandler receiver channel data is not available",
-                              e.description());
-                    break;
-                }
+                EventCMD::Stop => self.stop(),
+                EventCMD::None => {}
             }
         }
     }
@@ -0,0 +1,393 @@
+#![allow(dead_code)]
+extern crate rand;
+
+use helper::{Path, NetHelper, Log};
+use event::Compression;
+use error::Error;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Path length is written in lower 3 bytes of the Path field length
+/// and highest byte is keeping event flags
+const EVENT_PATH_LEN_MASK: u32 = 0x00FF_FFFF;
+const EVENT_FLAGS_SHIFT: u32 = 24;
+
+/// Event encoding without header, used by API version 1 peers
+/// Event ID and hop limit are lost with this encoding, so routing loops through these peers are not detected
+pub const EVENT_VERSION_LEGACY: u8 = 1;
+/// Event encoding with header field right after the Path field
+pub const EVENT_VERSION_HEADER: u8 = 2;
+
+/// Event flag showing that encoded event is having header field
+pub const EVENT_FLAG_HEADER: u8 = 0x80;
+
+/// Fixed part of the header: event ID, timestamp, hops and count of key/value pairs
+/// Header field is length prefixed, so newer versions could add fields after key/value pairs
+/// and older ones would just skip them
+const EVENT_HEADER_FIXED_LEN: usize = 8 + 8 + 1 + 4;
+
+pub struct Event {
+    pub path: Path,
+    pub name: String,
+    pub from: String,
+    pub target: String,
+    pub data: Vec<u8>,
+    // flags from event header, like compression of event data
+    pub flags: u8,
+    // unique event ID given by the first Node which got this event, 0 if it's not set yet
+    pub id: u64,
+    // milliseconds since UNIX epoch when event entered into the tree
+    pub timestamp: u64,
+    // count of Nodes which transferred this event
+    pub hops: u8,
+    // how many times event could be forwarded by Nodes, 0 means it shouldn't be forwarded anymore
+    // it's written after key/value pairs in the header, None if it's not set yet
+    pub ttl: Option<u8>,
+    // custom key/value metadata, transferred together with event
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Default for Event {
+    #[inline(always)]
+    fn default() -> Event {
+        Event {
+            path: Path::new(),
+            name: String::new(),
+            from: String::new(),
+            target: String::new(),
+            data: vec![],
+            flags: 0,
+            id: 0,
+            timestamp: 0,
+            hops: 0,
+            ttl: None,
+            headers: BTreeMap::new(),
+        }
+    }
+}
+
+impl Event {
+    /// Giving unique ID, timestamp and hop limit to the event, if it doesn't have them yet
+    pub fn stamp(&mut self, ttl: u8) {
+        if self.ttl.is_none() {
+            self.ttl = Some(ttl);
+        }
+
+        if self.id != 0 {
+            return;
+        }
+
+        while self.id == 0 {
+            self.id = rand::random::<u64>();
+        }
+
+        self.timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() * 1000 + u64::from(d.subsec_millis()),
+            Err(_) => 0
+        };
+    }
+
+    #[inline(always)]
+    pub fn from_raw(data: &[u8]) -> Result<Event, Error> {
+        let mut offset: usize = 0;
+        let mut ev = Event::default();
+        let data_len = data.len();
+
+        // Reading Path Field from data, with event flags in the highest byte of its length
+        let path_word = NetHelper::bytes_to_u32(data, offset)?;
+        let path_len = (path_word & EVENT_PATH_LEN_MASK) as usize;
+        if offset + 4 + path_len > data_len {
+            return Err(Error::Protocol(format!("Event Path field is out of data bounds, {} bytes", path_len)));
+        }
+
+        ev.flags = (path_word >> EVENT_FLAGS_SHIFT) as u8;
+        ev.path = match Path::from_bytes(&data[(offset + 4)..(offset + 4 + path_len)]) {
+            Ok(p) => p,
+            Err(e) => return Err(Error::Protocol(format!("Event Path field is invalid -> {}", e)))
+        };
+        offset += 4 + path_len;
+
+        // Reading Event Header if it's given
+        if ev.flags & EVENT_FLAG_HEADER != 0 {
+            let (field_data, field_len) = Event::read_field(data, offset, data_len, "Header")?;
+            offset += field_len;
+            ev.read_header(field_data)?;
+        }
+
+        // Reading Event Name, From and Target fields
+        let (name, field_len) = Event::read_string(data, offset, data_len, "Name")?;
+        offset += field_len;
+        let (from, field_len) = Event::read_string(data, offset, data_len, "From")?;
+        offset += field_len;
+        let (target, field_len) = Event::read_string(data, offset, data_len, "Target")?;
+        offset += field_len;
+        ev.name = name;
+        ev.from = from;
+        ev.target = target;
+
+        // we got all fields in event
+        // so remaining data is for event data field
+        ev.data = Vec::from(&data[offset..]);
+        Ok(ev)
+    }
+
+    /// Reading BigEndian length prefixed field from given offset
+    /// Returns field bytes and total length consumed from data, including 4 bytes of length
+    #[inline(always)]
+    fn read_field<'a>(data: &'a [u8], offset: usize, data_len: usize, name: &str) -> Result<(&'a [u8], usize), Error> {
+        let field_len = NetHelper::bytes_to_u32(data, offset)? as usize;
+        if offset + 4 + field_len > data_len {
+            return Err(Error::Protocol(format!("Event {} field is out of data bounds, {} bytes", name, field_len)));
+        }
+
+        Ok((&data[(offset + 4)..(offset + 4 + field_len)], field_len + 4))
+    }
+
+    /// Reading length prefixed UTF-8 field from given offset
+    #[inline(always)]
+    fn read_string(data: &[u8], offset: usize, data_len: usize, name: &str) -> Result<(String, usize), Error> {
+        let (field_data, field_len) = Event::read_field(data, offset, data_len, name)?;
+        match String::from_utf8(Vec::from(field_data)) {
+            Ok(s) => Ok((s, field_len)),
+            Err(e) => Err(Error::Protocol(format!("Event {} field is not valid UTF-8 -> {}", name, e)))
+        }
+    }
+
+    /// Parsing header field data, which is not including its length
+    fn read_header(&mut self, data: &[u8]) -> Result<(), Error> {
+        if data.len() < EVENT_HEADER_FIXED_LEN {
+            return Err(Error::Protocol(format!("Event Header field is too short, {} bytes", data.len())));
+        }
+
+        let data = data.to_vec();
+        self.id = NetHelper::bytes_to_u64(&data, 0)?;
+        self.timestamp = NetHelper::bytes_to_u64(&data, 8)?;
+        self.hops = data[16];
+        let count = NetHelper::bytes_to_u32(&data, 17)?;
+        let mut offset = EVENT_HEADER_FIXED_LEN;
+        for _ in 0..count {
+            let (key, field_len) = Event::read_string(&data, offset, data.len(), "Header key")?;
+            offset += field_len;
+            let (value, field_len) = Event::read_string(&data, offset, data.len(), "Header value")?;
+            offset += field_len;
+            self.headers.insert(key, value);
+        }
+
+        // hop limit is written after key/value pairs, headers without it are keeping it unset
+        self.ttl = data.get(offset).cloned();
+
+        Ok(())
+    }
+
+    /// Making header field data, without its length
+    fn header_bytes(&self) -> Vec<u8> {
+        let ttl_len = if self.ttl.is_some() { 1 } else { 0 };
+        let len = self.headers.iter().fold(EVENT_HEADER_FIXED_LEN + ttl_len, |n, (k, v)| n + 8 + k.len() + v.len());
+        let mut buffer = vec![0; len];
+        let mut offset = NetHelper::u64_to_bytes(self.id, &mut buffer, 0);
+        offset += NetHelper::u64_to_bytes(self.timestamp, &mut buffer, offset);
+        buffer[offset] = self.hops;
+        offset += 1;
+        offset += NetHelper::u32_to_bytes(self.headers.len() as u32, &mut buffer, offset);
+        for (key, value) in &self.headers {
+            for field in &[key, value] {
+                offset += NetHelper::u32_to_bytes(field.len() as u32, &mut buffer, offset);
+                buffer[offset..offset + field.len()].copy_from_slice(field.as_bytes());
+                offset += field.len();
+            }
+        }
+
+        if let Some(ttl) = self.ttl {
+            buffer[offset] = ttl;
+        }
+        buffer
+    }
+
+    /// Converting event to raw data without header, which is readable by every peer
+    #[inline(always)]
+    pub fn to_raw(&self) -> Option<Vec<u8>> {
+        self.to_raw_with(EVENT_VERSION_LEGACY, Compression::None)
+    }
+
+    /// Converting event to raw data with given encoding version
+    /// and event data compressed by given algorithm
+    /// If compression failed, event data is kept uncompressed
+    pub fn to_raw_with(&self, version: u8, compression: Compression) -> Option<Vec<u8>> {
+        let mut flags = Compression::None.to_flags(self.flags) & !EVENT_FLAG_HEADER;
+        let header = if version >= EVENT_VERSION_HEADER {
+            flags |= EVENT_FLAG_HEADER;
+            self.header_bytes()
+        } else {
+            vec![]
+        };
+
+        if compression == Compression::None {
+            return self.raw_with_data(flags, &header, &self.data);
+        }
+
+        match compression.compress(&self.data) {
+            Ok(data) => self.raw_with_data(compression.to_flags(flags), &header, &data),
+            Err(e) => {
+                Log::error("Unable to compress event data", e.to_string().as_str());
+                self.raw_with_data(flags, &header, &self.data)
+            }
+        }
+    }
+
+    /// Decompressing event data if it's compressed based on event flags
+    /// Fails if data is not valid or larger than given max length
+    pub fn decompress(&mut self, max_len: usize) -> Result<(), Error> {
+        let compression = Compression::from_flags(self.flags);
+        if compression == Compression::None {
+            return Ok(());
+        }
+
+        self.data = compression.decompress(&self.data, max_len)?;
+        self.flags = Compression::None.to_flags(self.flags);
+        Ok(())
+    }
+
+    fn raw_with_data(&self, flags: u8, header: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+        let (path_len, name_len, from_len, target_len, event_data_len)
+              = (self.path.len(), self.name.len(), self.from.len(), self.target.len(), data.len());
+
+        // header field is written only if event flags are saying that
+        let header_len = if flags & EVENT_FLAG_HEADER != 0 { 4 + header.len() } else { 0 };
+
+        let data_len = 4 + path_len // path len endian and path bytes len
+            + header_len // header len endian and header bytes len
+            + 4 + name_len // name len endian and name bytes len
+            + 4 + from_len // from len endian and from bytes len
+            + 4 + target_len // target len endian and target bytes len
+            + event_data_len; // event data bytes len
+
+        // Adding +4 because we need to write also big endian total data length
+        let mut buffer: Vec<u8> = vec![0; data_len + 4];
+        let mut offset: usize = 0;
+
+        // writing total data length
+        offset += NetHelper::u32_to_bytes(data_len as u32, &mut buffer, offset);
+
+        // writing Event Path field
+        offset += NetHelper::u32_to_bytes(path_len as u32 | (flags as u32) << EVENT_FLAGS_SHIFT, &mut buffer, offset);
+        match self.path.to_bytes() {
+            Some(path_data) => {
+                buffer[offset..offset + path_len].copy_from_slice(path_data.as_slice());
+                offset += path_len;
+            }
+
+            None => {
+                Log::warn("Unable to convert Event Path into Data bytes", "");
+                return None;
+            }
+        }
+
+        // Writing Event Header Field
+        if header_len > 0 {
+            offset += NetHelper::u32_to_bytes(header.len() as u32, &mut buffer, offset);
+            buffer[offset..offset + header.len()].copy_from_slice(header);
+            offset += header.len();
+        }
+
+        // Writing Event Name Field
+        offset += NetHelper::u32_to_bytes(name_len as u32, &mut buffer, offset);
+        buffer[offset..offset + name_len].copy_from_slice(self.name.as_bytes());
+        offset += name_len;
+
+
+        // Writing Event From Field
+        offset += NetHelper::u32_to_bytes(from_len as u32, &mut buffer, offset);
+        buffer[offset..offset + from_len].copy_from_slice(self.from.as_bytes());
+        offset += from_len;
+
+
+        // Writing Event From Field
+        offset += NetHelper::u32_to_bytes(target_len as u32, &mut buffer, offset);
+        buffer[offset..offset + target_len].copy_from_slice(self.target.as_bytes());
+        offset += target_len;
+
+        // remaining should be out event data
+        buffer[offset..].copy_from_slice(data);
+
+        Some(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Event {
+        let mut ev = Event::default();
+        ev.path.mul(6);
+        ev.name = String::from("build.output");
+        ev.from = String::from("client");
+        ev.target = String::from("worker");
+        ev.data = b"event data ".repeat(100);
+        ev.id = 0x0102_0304_0506_0708;
+        ev.timestamp = 1_500_000_000_000;
+        ev.hops = 2;
+        ev.ttl = Some(5);
+        ev.headers.insert(String::from("key"), String::from("value"));
+        ev
+    }
+
+    /// Encoding event and parsing it back, without total length prefix
+    fn round_trip(ev: &Event, version: u8, compression: Compression) -> Event {
+        let raw = ev.to_raw_with(version, compression).unwrap();
+        assert_eq!(NetHelper::bytes_to_u32(&raw, 0).unwrap() as usize, raw.len() - 4);
+        let mut parsed = Event::from_raw(&raw[4..]).unwrap();
+        assert_eq!(Compression::from_flags(parsed.flags), compression);
+        parsed.decompress(ev.data.len()).unwrap();
+        parsed
+    }
+
+    #[test]
+    fn round_trip_all_versions_and_compressions() {
+        let ev = sample();
+        for &compression in &[Compression::None, Compression::Lz4, Compression::Zstd] {
+            for &version in &[EVENT_VERSION_LEGACY, EVENT_VERSION_HEADER] {
+                let parsed = round_trip(&ev, version, compression);
+                assert_eq!(parsed.path.to_bytes(), ev.path.to_bytes());
+                assert_eq!(parsed.name, ev.name);
+                assert_eq!(parsed.from, ev.from);
+                assert_eq!(parsed.target, ev.target);
+                assert_eq!(parsed.data, ev.data);
+                assert_eq!(Compression::from_flags(parsed.flags), Compression::None);
+
+                if version == EVENT_VERSION_LEGACY {
+                    assert_eq!(parsed.flags & EVENT_FLAG_HEADER, 0);
+                    assert_eq!((parsed.id, parsed.timestamp, parsed.hops, parsed.ttl), (0, 0, 0, None));
+                    assert!(parsed.headers.is_empty());
+                } else {
+                    assert_eq!((parsed.id, parsed.timestamp, parsed.hops, parsed.ttl), (ev.id, ev.timestamp, ev.hops, ev.ttl));
+                    assert_eq!(parsed.headers, ev.headers);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn header_without_ttl() {
+        let mut ev = sample();
+        ev.ttl = None;
+        let mut parsed = round_trip(&ev, EVENT_VERSION_HEADER, Compression::None);
+        assert_eq!(parsed.ttl, None);
+
+        // missing hop limit is given by the Node, even if event already has an ID
+        parsed.stamp(7);
+        assert_eq!((parsed.id, parsed.ttl), (ev.id, Some(7)));
+    }
+
+    #[test]
+    fn invalid_data() {
+        let raw = sample().to_raw_with(EVENT_VERSION_HEADER, Compression::None).unwrap();
+        assert!(Event::from_raw(&raw[4..20]).is_err());
+        assert!(Event::from_raw(&[0, 0, 0, 10]).is_err());
+
+        // path which isn't made of 8 byte parts, followed by empty name, from and target
+        let mut raw = vec![0, 0, 0, 3, 1, 2, 3];
+        raw.extend_from_slice(&[0; 12]);
+        assert!(Event::from_raw(&raw).is_err());
+    }
+}
@@ -1,3 +1,11 @@
-mod event;
+mod main;
+mod codec;
+mod compression;
+mod recent;
+mod handler;
 
-pub use self::event::Event;
\ No newline at end of file
+pub use self::main::{Event, EVENT_VERSION_LEGACY, EVENT_VERSION_HEADER};
+pub use self::codec::EventCodec;
+pub use self::compression::Compression;
+pub use self::recent::{RecentEvents, DroppedEvents};
+pub use self::handler::{EventHandler, EventCallback, EventCommand, EventCMD};
\ No newline at end of file
@@ -0,0 +1,79 @@
+#![allow(dead_code)]
+
+use helper::NetHelper;
+use std::collections::{HashSet, VecDeque};
+
+/// Bounded set of recently seen event IDs
+/// When it's full, the oldest ID is removed for every new one
+pub struct RecentEvents {
+    ids: HashSet<u64>,
+    // IDs in order of adding, for removing the oldest ones
+    order: VecDeque<u64>,
+    capacity: usize
+}
+
+/// Counters of events which were dropped by current Node instead of forwarding them
+#[derive(Default, Clone)]
+pub struct DroppedEvents {
+    // events which reached their hop limit
+    pub hop_limit: u64,
+    // events with already seen ID, which are coming back because of routing loop
+    pub duplicate: u64
+}
+
+impl RecentEvents {
+    pub fn new(capacity: usize) -> RecentEvents {
+        RecentEvents {
+            ids: HashSet::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            capacity
+        }
+    }
+
+    /// Adding event ID to the cache
+    /// Returns false if we already have it, so event is a duplicate
+    pub fn insert(&mut self, id: u64) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+
+        if !self.ids.insert(id) {
+            return false;
+        }
+
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.ids.remove(&old);
+            }
+        }
+
+        true
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+impl DroppedEvents {
+    /// Encoding counters as a list of length prefixed names with 8 bytes BigEndian values
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        for &(name, value) in &[("hop_limit", self.hop_limit), ("duplicate", self.duplicate)] {
+            let mut field = vec![0; 4 + name.len() + 8];
+            NetHelper::u32_to_bytes(name.len() as u32, &mut field, 0);
+            field[4..4 + name.len()].copy_from_slice(name.as_bytes());
+            NetHelper::u64_to_bytes(value, &mut field, 4 + name.len());
+            buffer.extend_from_slice(&field);
+        }
+
+        buffer
+    }
+}
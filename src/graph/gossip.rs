@@ -0,0 +1,163 @@
+#![allow(dead_code)]
+
+use node::Node;
+use network::Networking;
+use event::Event;
+use graph::{NodeState, GRAPH_NODE_JOIN, GRAPH_NODE_LEAVE, GRAPH_NODE_RELATIONS, GRAPH_NODE_SUBSCRIPTIONS};
+use helper::{Log, NetHelper, Path};
+
+/// Gossip trait for keeping Graph state in sync across the tree
+/// Every Node is announcing his own state with increasing version number
+/// and forwarding states of other Nodes if they are newer than the ones he already have
+pub trait Gossip {
+    /// Sending our full Graph to newly connected Node
+    /// and announcing our new relations to the rest of the tree
+    fn gossip_join(&mut self, token: &str);
+
+    /// Announcing that connection with given Node is closed
+    /// if Node is not reachable anymore, it is announced as left with his latest version
+    fn gossip_leave(&mut self, token: &str, left_version: Option<u64>);
+
+    /// Announcing our subscriptions after they have been changed
+    fn gossip_subscriptions(&mut self);
+
+    /// Handling Graph event received from given connection token
+    fn on_gossip_event(&mut self, conn_token: &str, event: &Event);
+
+    /// Sending state of given Node as an event with given name and path
+    fn gossip_send_state(&mut self, name: &str, token: &str, path: Path);
+
+    /// Making path for all directly connected Nodes, except given one
+    fn gossip_neighbours_path(&self, except: &str) -> Path;
+}
+
+impl Gossip for Node {
+    fn gossip_join(&mut self, token: &str) {
+        self.graph.touch();
+
+        let value = match self.graph.get(token) {
+            Some(n) => n.value,
+            None => return
+        };
+
+        // new Node is getting everything what we know about the tree
+        // nearest Nodes are going first, so that receiver could reach every next one
+        let tokens: Vec<String> = self.graph.walk().into_iter().filter(|t| t != token).collect();
+        for t in tokens {
+            let mut path = Path::new();
+            path.mul(value);
+            self.gossip_send_state(GRAPH_NODE_JOIN, &t, path);
+        }
+
+        // others are getting only our new state
+        let path = self.gossip_neighbours_path(token);
+        let self_token = self.token.clone();
+        self.gossip_send_state(GRAPH_NODE_JOIN, &self_token, path);
+    }
+
+    fn gossip_leave(&mut self, token: &str, left_version: Option<u64>) {
+        self.graph.touch();
+
+        let self_token = self.token.clone();
+        let path = self.gossip_neighbours_path(token);
+        self.gossip_send_state(GRAPH_NODE_RELATIONS, &self_token, path);
+
+        if let Some(version) = left_version {
+            let mut ev = Event {
+                name: String::from(GRAPH_NODE_LEAVE),
+                from: String::from(token),
+                data: vec![0; 8],
+                ..Event::default()
+            };
+            NetHelper::u64_to_bytes(version, &mut ev.data, 0);
+            ev.path = self.gossip_neighbours_path(token);
+            if !ev.path.is_zero() {
+                self.emit(ev);
+            }
+        }
+    }
+
+    fn gossip_subscriptions(&mut self) {
+        self.graph.touch();
+        let self_token = self.token.clone();
+        let path = self.gossip_neighbours_path("");
+        self.gossip_send_state(GRAPH_NODE_SUBSCRIPTIONS, &self_token, path);
+    }
+
+    fn on_gossip_event(&mut self, conn_token: &str, event: &Event) {
+        let fresh = match event.name.as_str() {
+            GRAPH_NODE_JOIN | GRAPH_NODE_RELATIONS | GRAPH_NODE_SUBSCRIPTIONS => {
+                match NodeState::from_bytes(&event.from, &event.data) {
+                    Ok(state) => self.graph.apply_state(state),
+                    Err(e) => {
+                        Log::warn("Unable to parse Node state from Graph event", format!("{} -> {}", event.from, e).as_str());
+                        return;
+                    }
+                }
+            }
+
+            GRAPH_NODE_LEAVE => {
+                match NetHelper::bytes_to_u64(&event.data, 0) {
+                    Ok(version) => self.graph.leave(&event.from, version),
+                    Err(e) => {
+                        Log::warn("Unable to parse Node version from Graph leave event", format!("{} -> {}", event.from, e).as_str());
+                        return;
+                    }
+                }
+            }
+
+            _ => false
+        };
+
+        // we already have this state, so the rest of the tree have it as well
+        if !fresh {
+            return;
+        }
+
+        // relations could be changed, so cleaning up everything not reachable
+        self.graph.prune();
+
+        let ev = Event {
+            name: event.name.clone(),
+            from: event.from.clone(),
+            data: event.data.clone(),
+            path: self.gossip_neighbours_path(conn_token),
+            ..Event::default()
+        };
+        self.emit(ev);
+    }
+
+    fn gossip_send_state(&mut self, name: &str, token: &str, path: Path) {
+        if path.is_zero() {
+            return;
+        }
+
+        let state = match self.graph.state(token) {
+            Some(s) => s,
+            None => return
+        };
+
+        let ev = Event {
+            name: String::from(name),
+            from: String::from(token),
+            data: state.to_bytes(),
+            path,
+            ..Event::default()
+        };
+        self.emit(ev);
+    }
+
+    #[inline(always)]
+    fn gossip_neighbours_path(&self, except: &str) -> Path {
+        let mut path = Path::new();
+        for (token, conn) in &self.connections {
+            if conn.value == 0 || token == except {
+                continue;
+            }
+
+            path.mul(conn.value);
+        }
+
+        path
+    }
+}
@@ -0,0 +1,774 @@
+#![allow(dead_code)]
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use event::Event;
+use helper::{Path, NetHelper, NameTrie};
+use error::Error;
+
+pub type Subscriptions = BTreeMap<String, Vec<String>>;
+
+/// Kind of the name which Node could be subscribed to
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SubscriptionType {
+    Event,
+    Channel,
+    Group,
+    Tag
+}
+
+/// TreeNode keeping information about individual nodes information
+/// Including subscribed events, channels, groups list
+pub struct TreeNode {
+    // Token for this Node
+    pub token: String,
+    // Prime value for this Node
+    pub value: u64,
+    // List of node tokens who are connected to this Node
+    pub relations: Vec<String>,
+    // Sequence number of the latest state which we got for this Node
+    pub version: u64
+}
+
+/// Snapshot of the single Node state
+/// which is transferred between Nodes for keeping graphs in sync
+pub struct NodeState {
+    pub token: String,
+    pub version: u64,
+    pub value: u64,
+    pub relations: Vec<String>,
+    pub events: Vec<String>,
+    pub channels: Vec<String>,
+    pub groups: Vec<String>,
+    pub tags: Vec<String>
+}
+
+/// Graph struct is the main structure for keeping tree state information
+/// And keeping events, channels, group names for tree
+pub struct Graph {
+    // Token of the Node who owns this Graph
+    token: String,
+
+    // Map of Nodes inside this Graph
+    // Key -> Node Token
+    // Value -> TreeNode
+    nodes: BTreeMap<String, TreeNode>,
+
+    // Nodes which are announced as left from the tree
+    // Key -> Node Token
+    // Value -> latest known version of the Node, older states would be ignored
+    removed: BTreeMap<String, u64>,
+
+    // Events Map inside this Graph
+    // Key -> Event Name
+    // Value -> Node Token
+    events: Subscriptions,
+
+    // Event names from events map, including wildcard patterns
+    // for finding subscriptions matching to the event name
+    event_patterns: NameTrie,
+
+    // Channels Map inside this Graph
+    // Key -> Channel Name
+    // Value -> Node Token
+    channels: Subscriptions,
+
+    // Groups Map inside this Graph
+    // Key -> Group Name
+    // Value -> Node Token
+    groups: Subscriptions,
+
+    // Tags Map inside this Graph
+    // Key -> Tag Name
+    // Value -> Node Token
+    tags: Subscriptions,
+
+    // Subscriptions of API clients connected to current Node
+    // Key -> API client Token
+    // Value -> list of subscribed names
+    clients: BTreeMap<String, Vec<(SubscriptionType, String)>>,
+
+    // Round Robin index for choosing one of the Nodes subscribed to group
+    // Key -> Group Name
+    group_indexes: BTreeMap<String, usize>,
+}
+
+impl SubscriptionType {
+    /// Getting subscription type from his byte representation in API protocol
+    #[inline(always)]
+    pub fn from_byte(b: u8) -> Option<SubscriptionType> {
+        match b {
+            1 => Some(SubscriptionType::Event),
+            2 => Some(SubscriptionType::Channel),
+            3 => Some(SubscriptionType::Group),
+            4 => Some(SubscriptionType::Tag),
+            _ => None
+        }
+    }
+
+    #[inline(always)]
+    pub fn to_byte(self) -> u8 {
+        match self {
+            SubscriptionType::Event => 1,
+            SubscriptionType::Channel => 2,
+            SubscriptionType::Group => 3,
+            SubscriptionType::Tag => 4,
+        }
+    }
+}
+
+impl TreeNode {
+    #[inline(always)]
+    pub fn new(token: String, value: u64) -> TreeNode {
+        TreeNode {
+            token,
+            value,
+            relations: vec![],
+            version: 0
+        }
+    }
+}
+
+impl NodeState {
+    /// Converting state to bytes for sending it as an Event data
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = vec![0; 16];
+        NetHelper::u64_to_bytes(self.version, &mut buffer, 0);
+        NetHelper::u64_to_bytes(self.value, &mut buffer, 8);
+        for list in &[&self.relations, &self.events, &self.channels, &self.groups, &self.tags] {
+            NodeState::write_list(&mut buffer, list);
+        }
+
+        buffer
+    }
+
+    /// Parsing state from given bytes, token is not a part of the data
+    /// because it is transferred as an Event "from" field
+    pub fn from_bytes(token: &str, data: &[u8]) -> Result<NodeState, Error> {
+        let version = NetHelper::bytes_to_u64(data, 0)?;
+        let value = NetHelper::bytes_to_u64(data, 8)?;
+
+        let mut offset = 16;
+        let relations = NodeState::read_list(data, &mut offset)?;
+        let events = NodeState::read_list(data, &mut offset)?;
+        let channels = NodeState::read_list(data, &mut offset)?;
+        let groups = NodeState::read_list(data, &mut offset)?;
+        let tags = NodeState::read_list(data, &mut offset)?;
+
+        Ok(NodeState {
+            token: String::from(token),
+            version,
+            value,
+            relations,
+            events,
+            channels,
+            groups,
+            tags
+        })
+    }
+
+    /// Writing list of strings as a BigEndian count
+    /// and BigEndian length prefixed strings
+    fn write_list(buffer: &mut Vec<u8>, list: &[String]) {
+        let mut offset = buffer.len();
+        buffer.resize(offset + 4 + list.iter().map(|s| s.len() + 4).sum::<usize>(), 0);
+        offset += NetHelper::u32_to_bytes(list.len() as u32, buffer, offset);
+        for s in list {
+            offset += NetHelper::u32_to_bytes(s.len() as u32, buffer, offset);
+            buffer[offset..offset + s.len()].copy_from_slice(s.as_bytes());
+            offset += s.len();
+        }
+    }
+
+    fn read_list(data: &[u8], offset: &mut usize) -> Result<Vec<String>, Error> {
+        let count = NetHelper::bytes_to_u32(data, *offset)?;
+        *offset += 4;
+
+        let mut list = vec![];
+        for _ in 0..count {
+            let len = NetHelper::bytes_to_u32(data, *offset)? as usize;
+            if *offset + 4 + len > data.len() {
+                return Err(Error::Protocol(String::from("Node state list item is out of data bounds")));
+            }
+
+            match String::from_utf8(Vec::from(&data[(*offset + 4)..(*offset + 4 + len)])) {
+                Ok(s) => list.push(s),
+                Err(_) => return Err(Error::Protocol(String::from("Node state list item is not a valid UTF-8 string")))
+            }
+            *offset += 4 + len;
+        }
+
+        Ok(list)
+    }
+}
+
+impl Graph {
+    /// Creating new graph system for current Node
+    /// Current Node is always the first member of the graph
+    pub fn new(token: &str, value: u64) -> Graph {
+        // starting version from current time, so that after restart
+        // our new states wouldn't be ignored as an old ones
+        let mut node = TreeNode::new(String::from(token), value);
+        node.version = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() * 1000 + u64::from(d.subsec_millis()),
+            Err(_) => 0
+        };
+
+        let mut nodes = BTreeMap::new();
+        nodes.insert(String::from(token), node);
+
+        Graph {
+            token: String::from(token),
+            nodes,
+            removed: BTreeMap::new(),
+            events: Subscriptions::new(),
+            event_patterns: NameTrie::new(),
+            channels: Subscriptions::new(),
+            groups: Subscriptions::new(),
+            tags: Subscriptions::new(),
+            clients: BTreeMap::new(),
+            group_indexes: BTreeMap::new()
+        }
+    }
+
+    /// Token of the Node who owns this Graph
+    #[inline(always)]
+    pub fn token(&self) -> &str {
+        self.token.as_str()
+    }
+
+    #[inline(always)]
+    pub fn get(&self, token: &str) -> Option<&TreeNode> {
+        self.nodes.get(token)
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, token: &str) -> bool {
+        self.nodes.contains_key(token)
+    }
+
+    #[inline(always)]
+    pub fn nodes(&self) -> &BTreeMap<String, TreeNode> {
+        &self.nodes
+    }
+
+    /// Adding node to graph, or updating his value if we already have it
+    pub fn add_node(&mut self, token: &str, value: u64) {
+        if let Some(node) = self.nodes.get_mut(token) {
+            node.value = value;
+            return;
+        }
+
+        self.nodes.insert(String::from(token), TreeNode::new(String::from(token), value));
+    }
+
+    /// Removing node from graph with all relations and subscriptions pointing to it
+    pub fn rm_node(&mut self, token: &str) {
+        if self.nodes.remove(token).is_none() {
+            return;
+        }
+
+        for node in self.nodes.values_mut() {
+            node.relations.retain(|t| t != token);
+        }
+
+        self.rm_token_subscriptions(token);
+    }
+
+    /// Making relation between 2 nodes in both directions
+    /// Both of the nodes should be already in the graph
+    pub fn add_relation(&mut self, token1: &str, token2: &str) -> bool {
+        if token1 == token2 || !self.nodes.contains_key(token1) || !self.nodes.contains_key(token2) {
+            return false;
+        }
+
+        Graph::add_node_relation(self.nodes.get_mut(token1).unwrap(), token2);
+        Graph::add_node_relation(self.nodes.get_mut(token2).unwrap(), token1);
+        true
+    }
+
+    /// Removing relation between 2 nodes in both directions
+    pub fn rm_relation(&mut self, token1: &str, token2: &str) {
+        if let Some(node) = self.nodes.get_mut(token1) {
+            node.relations.retain(|t| t != token2);
+        }
+
+        if let Some(node) = self.nodes.get_mut(token2) {
+            node.relations.retain(|t| t != token1);
+        }
+    }
+
+    /// Replacing node value and relations with the state announced by remote node
+    /// Unknown related nodes are kept only as tokens, until they will announce themselves
+    pub fn update_node(&mut self, token: &str, value: u64, relations: Vec<String>) {
+        self.add_node(token, value);
+        let node = self.nodes.get_mut(token).unwrap();
+        node.relations = relations;
+        node.relations.retain(|t| t != token);
+        node.relations.sort();
+        node.relations.dedup();
+    }
+
+    /// Increasing version of the current Node state
+    /// this should be called on every local change which needs to be announced
+    pub fn touch(&mut self) -> u64 {
+        let node = self.nodes.get_mut(&self.token).unwrap();
+        node.version += 1;
+        node.version
+    }
+
+    /// Checking if we already have the same or newer state for given Node
+    pub fn is_stale(&self, token: &str, version: u64) -> bool {
+        if let Some(node) = self.nodes.get(token) {
+            return version <= node.version;
+        }
+
+        match self.removed.get(token) {
+            Some(v) => version <= *v,
+            None => false
+        }
+    }
+
+    /// Making state snapshot for given Node
+    pub fn state(&self, token: &str) -> Option<NodeState> {
+        let node = self.nodes.get(token)?;
+        let names = |subscriptions: &Subscriptions| -> Vec<String> {
+            subscriptions.iter()
+                         .filter(|&(_, tokens)| tokens.iter().any(|t| t == token))
+                         .map(|(name, _)| name.clone())
+                         .collect()
+        };
+
+        Some(NodeState {
+            token: node.token.clone(),
+            version: node.version,
+            value: node.value,
+            relations: node.relations.clone(),
+            events: names(&self.events),
+            channels: names(&self.channels),
+            groups: names(&self.groups),
+            tags: names(&self.tags)
+        })
+    }
+
+    /// Applying Node state received from the tree
+    /// Returns false if given state is older than the one we already have
+    pub fn apply_state(&mut self, state: NodeState) -> bool {
+        if state.token == self.token || self.is_stale(&state.token, state.version) {
+            return false;
+        }
+
+        self.removed.remove(&state.token);
+        self.update_node(&state.token, state.value, state.relations);
+        self.nodes.get_mut(&state.token).unwrap().version = state.version;
+
+        self.rm_token_subscriptions(&state.token);
+
+        for (kind, names) in [(SubscriptionType::Event, state.events),
+                              (SubscriptionType::Channel, state.channels),
+                              (SubscriptionType::Group, state.groups),
+                              (SubscriptionType::Tag, state.tags)] {
+            for name in names {
+                self.subscribe(kind, &name, &state.token);
+            }
+        }
+
+        true
+    }
+
+    /// Removing Node which is announced as left from the tree
+    /// Returns false if we already have newer state for that Node
+    pub fn leave(&mut self, token: &str, version: u64) -> bool {
+        if token == self.token {
+            return false;
+        }
+
+        if let Some(node) = self.nodes.get(token) {
+            if node.version > version {
+                return false;
+            }
+        }
+
+        match self.removed.get(token) {
+            Some(v) if *v >= version => return false,
+            _ => {}
+        }
+
+        self.removed.insert(String::from(token), version);
+        self.rm_node(token);
+        true
+    }
+
+    /// Subscribing Node with given token to event, channel or group name
+    /// Event wildcard patterns are kept as is, Nodes without wildcards capability are matching them only as exact names
+    pub fn subscribe(&mut self, kind: SubscriptionType, name: &str, token: &str) {
+        {
+            let tokens = self.subscriptions_mut(kind).entry(String::from(name)).or_default();
+            if !tokens.iter().any(|t| t == token) {
+                tokens.push(String::from(token));
+            }
+        }
+
+        if kind == SubscriptionType::Event {
+            self.event_patterns.insert(name);
+        }
+    }
+
+    /// Removing Node subscription for given event, channel or group name
+    pub fn unsubscribe(&mut self, kind: SubscriptionType, name: &str, token: &str) {
+        let subscriptions = self.subscriptions_mut(kind);
+        let is_empty = match subscriptions.get_mut(name) {
+            Some(tokens) => {
+                tokens.retain(|t| t != token);
+                tokens.is_empty()
+            }
+            None => return
+        };
+
+        if is_empty {
+            subscriptions.remove(name);
+            if kind == SubscriptionType::Event {
+                self.event_patterns.remove(name);
+            }
+        }
+    }
+
+    /// Subscribing API client connected to current Node
+    /// Returns true if current Node subscriptions have been changed because of it
+    pub fn subscribe_client(&mut self, client: &str, kind: SubscriptionType, name: &str) -> bool {
+        {
+            let subscriptions = self.clients.entry(String::from(client)).or_default();
+            if subscriptions.iter().any(|&(k, ref n)| k == kind && n == name) {
+                return false;
+            }
+            subscriptions.push((kind, String::from(name)));
+        }
+
+        if self.subscribers(kind, name).contains(&self.token) {
+            return false;
+        }
+
+        let token = self.token.clone();
+        self.subscribe(kind, name, &token);
+        true
+    }
+
+    /// Removing API client subscription
+    /// Returns true if current Node subscriptions have been changed because of it
+    pub fn unsubscribe_client(&mut self, client: &str, kind: SubscriptionType, name: &str) -> bool {
+        let removed = match self.clients.get_mut(client) {
+            Some(subscriptions) => {
+                let count = subscriptions.len();
+                subscriptions.retain(|&(k, ref n)| k != kind || n != name);
+                count != subscriptions.len()
+            }
+            None => false
+        };
+
+        if !removed {
+            return false;
+        }
+
+        if self.clients.get(client).map(|s| s.is_empty()).unwrap_or(false) {
+            self.clients.remove(client);
+        }
+
+        // other API clients still need this subscription
+        if !self.clients_for(kind, name).is_empty() {
+            return false;
+        }
+
+        let token = self.token.clone();
+        self.unsubscribe(kind, name, &token);
+        true
+    }
+
+    /// Removing all subscriptions of given API client
+    /// Returns true if current Node subscriptions have been changed because of it
+    pub fn rm_client(&mut self, client: &str) -> bool {
+        let subscriptions = match self.clients.get(client) {
+            Some(s) => s.clone(),
+            None => return false
+        };
+
+        let mut changed = false;
+        for (kind, name) in subscriptions {
+            changed = self.unsubscribe_client(client, kind, &name) || changed;
+        }
+
+        changed
+    }
+
+    /// Getting all subscriptions of given API client
+    #[inline(always)]
+    pub fn client_subscriptions(&self, client: &str) -> &[(SubscriptionType, String)] {
+        match self.clients.get(client) {
+            Some(subscriptions) => subscriptions.as_slice(),
+            None => &[]
+        }
+    }
+
+    /// Getting API client tokens subscribed to given name
+    pub fn clients_for(&self, kind: SubscriptionType, name: &str) -> Vec<String> {
+        self.clients.iter()
+                    .filter(|&(_, subscriptions)| subscriptions.iter().any(|&(k, ref n)| k == kind && n == name))
+                    .map(|(client, _)| client.clone())
+                    .collect()
+    }
+
+    /// Getting list of node tokens subscribed to given name
+    #[inline(always)]
+    pub fn subscribers(&self, kind: SubscriptionType, name: &str) -> &[String] {
+        match self.subscriptions(kind).get(name) {
+            Some(tokens) => tokens.as_slice(),
+            None => &[]
+        }
+    }
+
+    /// Finding all nodes who should receive given event
+    /// If event have a target, then it is a Node token, Channel, Group or Tag name
+    /// otherwise event would be delivered to all subscribers of event name, including wildcard ones
+    /// Group event is going only to one of the subscribed Nodes, chosen by Round Robin
+    pub fn event_subscribers(&mut self, event: &Event) -> BTreeSet<String> {
+        let mut tokens = BTreeSet::new();
+        if event.target.is_empty() {
+            for pattern in self.event_patterns.matches(&event.name) {
+                tokens.extend(self.subscribers(SubscriptionType::Event, pattern).iter().cloned());
+            }
+            return tokens;
+        }
+
+        if self.nodes.contains_key(&event.target) {
+            tokens.insert(event.target.clone());
+        }
+
+        tokens.extend(self.subscribers(SubscriptionType::Channel, &event.target).iter().cloned());
+        tokens.extend(self.subscribers(SubscriptionType::Tag, &event.target).iter().cloned());
+        if let Some(token) = self.pick_group_node(&event.target) {
+            tokens.insert(token);
+        }
+
+        tokens
+    }
+
+    /// Choosing one of the Nodes subscribed to given group with Round Robin
+    /// Configured balance strategy is applied only to API clients of the chosen Node
+    fn pick_group_node(&mut self, group: &str) -> Option<String> {
+        let count = self.subscribers(SubscriptionType::Group, group).len();
+        if count == 0 {
+            return None;
+        }
+
+        let index = self.group_indexes.entry(String::from(group)).or_insert(0);
+        if *index >= count {
+            *index = 0;
+        }
+
+        let i = *index;
+        *index += 1;
+        Some(self.subscribers(SubscriptionType::Group, group)[i].clone())
+    }
+
+    /// Checking if given path is going to any other Node subscribed to given group
+    /// in that case current Node is only on the way of group event
+    pub fn group_in_path(&self, group: &str, path: &Path) -> bool {
+        self.subscribers(SubscriptionType::Group, group)
+            .iter()
+            .filter(|t| **t != self.token)
+            .filter_map(|t| self.nodes.get(t))
+            .any(|n| path.dividable(n.value))
+    }
+
+    /// Finding API clients of current Node who should receive given event
+    /// using the same rules as for Node subscriptions
+    /// Group members are not included, because only one of them should get the event
+    pub fn event_clients(&self, event: &Event) -> BTreeSet<String> {
+        let mut clients = BTreeSet::new();
+        if event.target.is_empty() || event.target == self.token {
+            for pattern in self.event_patterns.matches(&event.name) {
+                clients.extend(self.clients_for(SubscriptionType::Event, pattern));
+            }
+            return clients;
+        }
+
+        if self.clients.contains_key(&event.target) {
+            clients.insert(event.target.clone());
+        }
+
+        for kind in &[SubscriptionType::Channel, SubscriptionType::Tag] {
+            clients.extend(self.clients_for(*kind, &event.target));
+        }
+
+        clients
+    }
+
+    /// Calculating Path for given event
+    /// Path is a multiplication of all node values on the way to every subscriber
+    /// If current Node is subscribed too, his value would be included as well
+    pub fn resolve_path(&mut self, event: &Event) -> Path {
+        let mut values = BTreeSet::new();
+        for token in self.event_subscribers(event) {
+            if let Some(route) = self.route(&token) {
+                values.extend(route);
+            }
+        }
+
+        let mut path = Path::new();
+        for value in values {
+            path.mul(value);
+        }
+
+        path
+    }
+
+    /// Getting node values on the shortest way from current Node to given one
+    /// Current Node value is included only if route is pointing to current Node
+    pub fn route(&self, token: &str) -> Option<Vec<u64>> {
+        if token == self.token {
+            return self.nodes.get(token).map(|n| vec![n.value]);
+        }
+
+        let mut parents: BTreeMap<String, String> = BTreeMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(self.token.clone());
+
+        while let Some(current) = queue.pop_front() {
+            if current == token {
+                break;
+            }
+
+            let node = match self.nodes.get(&current) {
+                Some(n) => n,
+                None => continue
+            };
+
+            for rel in &node.relations {
+                if *rel == self.token || parents.contains_key(rel) || !self.nodes.contains_key(rel) {
+                    continue;
+                }
+
+                parents.insert(rel.clone(), current.clone());
+                queue.push_back(rel.clone());
+            }
+        }
+
+        // walking back from destination to current Node
+        let mut values = vec![];
+        let mut current = String::from(token);
+        while current != self.token {
+            let node = self.nodes.get(&current)?;
+            // Node with 0 value is not routable
+            if node.value == 0 {
+                return None;
+            }
+
+            values.push(node.value);
+            current = parents.get(&current)?.clone();
+        }
+
+        Some(values)
+    }
+
+    /// Removing all nodes which are not reachable from current Node anymore
+    /// Returns list of removed node tokens
+    pub fn prune(&mut self) -> Vec<String> {
+        let reachable = self.reachable();
+        let removed: Vec<String> = self.nodes.keys()
+                                            .filter(|t| !reachable.contains(*t))
+                                            .cloned()
+                                            .collect();
+        for token in &removed {
+            self.rm_node(token);
+        }
+
+        removed
+    }
+
+    /// Getting node tokens reachable from current Node
+    /// ordered by distance, starting from current Node itself
+    pub fn walk(&self) -> Vec<String> {
+        let mut visited = BTreeSet::new();
+        let mut order = vec![];
+        let mut queue = VecDeque::new();
+        visited.insert(self.token.clone());
+        queue.push_back(self.token.clone());
+
+        while let Some(token) = queue.pop_front() {
+            let node = match self.nodes.get(&token) {
+                Some(n) => n,
+                None => continue
+            };
+
+            for rel in &node.relations {
+                if self.nodes.contains_key(rel) && visited.insert(rel.clone()) {
+                    queue.push_back(rel.clone());
+                }
+            }
+
+            order.push(token);
+        }
+
+        order
+    }
+
+    #[inline(always)]
+    fn reachable(&self) -> BTreeSet<String> {
+        self.walk().into_iter().collect()
+    }
+
+    #[inline(always)]
+    fn subscriptions(&self, kind: SubscriptionType) -> &Subscriptions {
+        match kind {
+            SubscriptionType::Event => &self.events,
+            SubscriptionType::Channel => &self.channels,
+            SubscriptionType::Group => &self.groups,
+            SubscriptionType::Tag => &self.tags,
+        }
+    }
+
+    #[inline(always)]
+    fn subscriptions_mut(&mut self, kind: SubscriptionType) -> &mut Subscriptions {
+        match kind {
+            SubscriptionType::Event => &mut self.events,
+            SubscriptionType::Channel => &mut self.channels,
+            SubscriptionType::Group => &mut self.groups,
+            SubscriptionType::Tag => &mut self.tags,
+        }
+    }
+
+    #[inline(always)]
+    fn add_node_relation(node: &mut TreeNode, token: &str) {
+        if !node.relations.iter().any(|t| t == token) {
+            node.relations.push(String::from(token));
+        }
+    }
+
+    /// Removing given Node token from all subscriptions
+    fn rm_token_subscriptions(&mut self, token: &str) {
+        for name in Graph::rm_from_subscriptions(&mut self.events, token) {
+            self.event_patterns.remove(&name);
+        }
+
+        for subscriptions in &mut [&mut self.channels, &mut self.groups, &mut self.tags] {
+            Graph::rm_from_subscriptions(subscriptions, token);
+        }
+    }
+
+    /// Removing token from subscriptions, returns names which don't have any subscriber anymore
+    #[inline(always)]
+    fn rm_from_subscriptions(subscriptions: &mut Subscriptions, token: &str) -> Vec<String> {
+        for tokens in subscriptions.values_mut() {
+            tokens.retain(|t| t != token);
+        }
+
+        let empty: Vec<String> = subscriptions.iter()
+                                              .filter(|&(_, tokens)| tokens.is_empty())
+                                              .map(|(name, _)| name.clone())
+                                              .collect();
+        for name in &empty {
+            subscriptions.remove(name);
+        }
+
+        empty
+    }
+}
@@ -1 +1,16 @@
-mod graph;
\ No newline at end of file
+mod main;
+mod gossip;
+
+pub use self::main::{Graph, NodeState, SubscriptionType};
+pub use self::gossip::Gossip;
+
+/// Prefix for the reserved event names used by Nodes for keeping Graph in sync
+pub const GRAPH_EVENT_PREFIX: &str = "_ts_graph_";
+/// Node got new relation, or we are sending Node state to newly connected Node
+pub const GRAPH_NODE_JOIN: &str = "_ts_graph_join";
+/// Node is not reachable anymore
+pub const GRAPH_NODE_LEAVE: &str = "_ts_graph_leave";
+/// Node lost one of his relations
+pub const GRAPH_NODE_RELATIONS: &str = "_ts_graph_relations";
+/// Node subscriptions have been changed
+pub const GRAPH_NODE_SUBSCRIPTIONS: &str = "_ts_graph_subscriptions";
\ No newline at end of file
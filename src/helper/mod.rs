@@ -1,7 +1,9 @@
 mod logging;
 mod net;
 mod path;
+mod trie;
 
 pub use self::logging::Log;
 pub use self::net::NetHelper;
-pub use self::path::Path;
\ No newline at end of file
+pub use self::path::Path;
+pub use self::trie::NameTrie;
\ No newline at end of file
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use std::mem;
+use error::Error;
 
 /// helper functions for network operations
 pub struct NetHelper {
@@ -11,19 +11,12 @@ impl NetHelper {
     /// Returns 0 if it is unable to make it
     /// Returns final offset in buffer after adding bytes to it
     #[inline(always)]
-    pub fn u32_to_bytes(number: u32, buffer: &mut Vec<u8>, offset: usize) -> usize {
+    pub fn u32_to_bytes(number: u32, buffer: &mut [u8], offset: usize) -> usize {
         if buffer.len() - offset < 4 {
             return 0;
         }
 
-        let endian_bytes = unsafe {
-            mem::transmute::<u32, [u8; 4]>(number.to_be())
-        };
-
-        buffer[offset + 0] = endian_bytes[0];
-        buffer[offset + 1] = endian_bytes[1];
-        buffer[offset + 2] = endian_bytes[2];
-        buffer[offset + 3] = endian_bytes[3];
+        buffer[offset..offset + 4].copy_from_slice(&number.to_be_bytes());
 
         // how many bytes we have written
         4
@@ -33,23 +26,12 @@ impl NetHelper {
     /// Returns 0 if it is unable to make it
     /// Returns final offset in buffer after adding bytes to it
     #[inline(always)]
-    pub fn u64_to_bytes(number: u64, buffer: &mut Vec<u8>, offset: usize) -> usize {
+    pub fn u64_to_bytes(number: u64, buffer: &mut [u8], offset: usize) -> usize {
         if buffer.len() - offset < 8 {
             return 0;
         }
 
-        let endian_bytes = unsafe {
-            mem::transmute::<u64, [u8; 8]>(number.to_be())
-        };
-
-        buffer[offset + 0] = endian_bytes[0];
-        buffer[offset + 1] = endian_bytes[1];
-        buffer[offset + 2] = endian_bytes[2];
-        buffer[offset + 3] = endian_bytes[3];
-        buffer[offset + 4] = endian_bytes[4];
-        buffer[offset + 5] = endian_bytes[5];
-        buffer[offset + 6] = endian_bytes[6];
-        buffer[offset + 7] = endian_bytes[7];
+        buffer[offset..offset + 8].copy_from_slice(&number.to_be_bytes());
 
         // how many bytes we have written
         8
@@ -57,36 +39,30 @@ impl NetHelper {
 
     /// Parse given BigEndian bytes into u32 number
     #[inline(always)]
-    pub fn bytes_to_u32(buffer: &Vec<u8>, offset: usize) -> (bool, u32) {
-        if buffer.len() + offset < 4 {
-            return (false, 0);
+    pub fn bytes_to_u32(buffer: &[u8], offset: usize) -> Result<u32, Error> {
+        if buffer.len() < offset + 4 {
+            return Err(NetHelper::out_of_bounds(buffer, offset, 4));
         }
 
-        (true, unsafe {
-            let a = [
-                buffer[offset + 0], buffer[offset + 1],
-                buffer[offset + 2], buffer[offset + 3],
-            ];
-            u32::from_be(mem::transmute::<[u8; 4], u32>(a))
-        })
+        let mut a = [0; 4];
+        a.copy_from_slice(&buffer[offset..offset + 4]);
+        Ok(u32::from_be_bytes(a))
     }
 
     /// Parse given BigEndian bytes into u64 number
     #[inline(always)]
-    pub fn bytes_to_u64(buffer: &Vec<u8>, offset: usize) -> (bool, u64) {
-        if buffer.len() + offset < 8 {
-            return (false, 0);
+    pub fn bytes_to_u64(buffer: &[u8], offset: usize) -> Result<u64, Error> {
+        if buffer.len() < offset + 8 {
+            return Err(NetHelper::out_of_bounds(buffer, offset, 8));
         }
 
-        (true, unsafe {
-            let a = [
-                buffer[offset + 0], buffer[offset + 1],
-                buffer[offset + 2], buffer[offset + 3],
-                buffer[offset + 4], buffer[offset + 5],
-                buffer[offset + 6], buffer[offset + 7],
-            ];
-            u64::from_be(mem::transmute::<[u8; 8], u64>(a))
-        })
+        let mut a = [0; 8];
+        a.copy_from_slice(&buffer[offset..offset + 8]);
+        Ok(u64::from_be_bytes(a))
+    }
+
+    fn out_of_bounds(buffer: &[u8], offset: usize, len: usize) -> Error {
+        Error::Protocol(format!("Unable to read {} bytes number at offset {} from {} bytes", len, offset, buffer.len()))
     }
 
     /// Checking if given Node value is valid or not
@@ -99,7 +75,7 @@ impl NetHelper {
             3 => true,
             _ => {
                 for i in 2..(value/2) {
-                    if value % i == 0 {
+                    if value.is_multiple_of(i) {
                         return false
                     }
                 }
@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 
-use std::u64::{MAX as u64MAX};
 use helper::NetHelper;
+use error::Error;
 
 /// Base struct for handling path information and processing it
 pub struct Path {
@@ -18,22 +18,18 @@ impl Path {
     }
 
     #[inline(always)]
-    pub fn from_bytes(buffer: &[u8]) -> Option<Path> {
-        if buffer.len() % 8 != 0 {
-            return None;
+    pub fn from_bytes(buffer: &[u8]) -> Result<Path, Error> {
+        if !buffer.len().is_multiple_of(8) {
+            return Err(Error::Protocol(format!("Path length should be a multiple of 8 bytes, got {}", buffer.len())));
         }
 
         let mut p = Path::new();
         for i in 0..(buffer.len() / 8) {
             let pos = i * 8;
-            let (converted, n) = NetHelper::bytes_to_u64(&Vec::from(buffer), pos);
-            if !converted {
-                return None;
-            }
-            p.parts.push(n);
+            p.parts.push(NetHelper::bytes_to_u64(&Vec::from(buffer), pos)?);
         }
 
-        Some(p)
+        Ok(p)
     }
 
     #[inline(always)]
@@ -65,13 +61,13 @@ impl Path {
 
         // if after multiplication our number will overflow u64
         // just adding new slot and keeping value there
-        if self.parts.len() == 0 {
+        if self.parts.is_empty() {
             self.parts.push(number);
             return;
         }
 
         let last_index = self.parts.len() - 1;
-        if self.parts[last_index] > u64MAX / number {
+        if self.parts[last_index] > u64::MAX / number {
             self.parts.push(number);
             return;
         }
@@ -87,7 +83,7 @@ impl Path {
         }
 
         for i in 0..self.parts.len() {
-            if self.parts[i] % number == 0 {
+            if self.parts[i].is_multiple_of(number) {
                 self.parts[i] /= number;
                 return true;
             }
@@ -103,7 +99,7 @@ impl Path {
         }
 
         for i in 0..self.parts.len() {
-            if self.parts[i] % number == 0 {
+            if self.parts[i].is_multiple_of(number) {
                 return true;
             }
         }
@@ -113,6 +109,6 @@ impl Path {
 
     #[inline(always)]
     pub fn is_zero(&self) -> bool {
-        !(self.parts.len() > 0 && self.parts[0] != 0)
+        self.parts.is_empty() || self.parts[0] == 0
     }
 }
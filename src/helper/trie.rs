@@ -0,0 +1,185 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+
+/// Separator of the hierarchical event name segments
+pub const NAME_SEPARATOR: char = '.';
+/// Wildcard matching exactly one segment of the name
+pub const NAME_WILDCARD_ONE: &str = "*";
+/// Wildcard matching one or more remaining segments of the name, it should be the last one
+pub const NAME_WILDCARD_REST: &str = ">";
+
+/// Trie of dotted name patterns, like "build.*.output" or "docker.>"
+/// Names without wildcards are matching only themselves
+#[derive(Default)]
+pub struct NameTrie {
+    // child nodes by name segment, including wildcards
+    children: BTreeMap<String, NameTrie>,
+    // full pattern ending at this node
+    pattern: Option<String>
+}
+
+impl NameTrie {
+    pub fn new() -> NameTrie {
+        NameTrie::default()
+    }
+
+    /// Checking if given pattern could be used for subscription
+    /// ">" wildcard is valid only as the last segment
+    pub fn is_valid(pattern: &str) -> bool {
+        let segments: Vec<&str> = pattern.split(NAME_SEPARATOR).collect();
+        segments.iter()
+                .position(|s| *s == NAME_WILDCARD_REST)
+                .map(|i| i == segments.len() - 1)
+                .unwrap_or(true)
+    }
+
+    /// Adding pattern to the trie
+    /// Returns false if it's already there
+    pub fn insert(&mut self, pattern: &str) -> bool {
+        let mut node = self;
+        for segment in pattern.split(NAME_SEPARATOR) {
+            node = node.children.entry(String::from(segment)).or_default();
+        }
+
+        if node.pattern.is_some() {
+            return false;
+        }
+
+        node.pattern = Some(String::from(pattern));
+        true
+    }
+
+    /// Removing pattern from the trie, with all nodes which are not needed anymore
+    /// Returns false if there is no such pattern
+    pub fn remove(&mut self, pattern: &str) -> bool {
+        let segments: Vec<&str> = pattern.split(NAME_SEPARATOR).collect();
+        self.remove_segments(&segments)
+    }
+
+    fn remove_segments(&mut self, segments: &[&str]) -> bool {
+        if segments.is_empty() {
+            return self.pattern.take().is_some();
+        }
+
+        let (removed, empty) = match self.children.get_mut(segments[0]) {
+            Some(child) => (child.remove_segments(&segments[1..]), child.is_empty()),
+            None => return false
+        };
+
+        if empty {
+            self.children.remove(segments[0]);
+        }
+
+        removed
+    }
+
+    /// Finding all patterns matching given name
+    pub fn matches(&self, name: &str) -> Vec<&str> {
+        let segments: Vec<&str> = name.split(NAME_SEPARATOR).collect();
+        let mut patterns = vec![];
+        self.collect(&segments, &mut patterns);
+        patterns
+    }
+
+    fn collect<'a>(&'a self, segments: &[&str], patterns: &mut Vec<&'a str>) {
+        if segments.is_empty() {
+            if let Some(ref p) = self.pattern {
+                patterns.push(p.as_str());
+            }
+            return;
+        }
+
+        if let Some(child) = self.children.get(segments[0]) {
+            child.collect(&segments[1..], patterns);
+        }
+
+        // exact segment could be the same as wildcard, it's already checked above
+        if segments[0] != NAME_WILDCARD_ONE {
+            if let Some(child) = self.children.get(NAME_WILDCARD_ONE) {
+                child.collect(&segments[1..], patterns);
+            }
+        }
+
+        if segments[0] != NAME_WILDCARD_REST {
+            if let Some(p) = self.children.get(NAME_WILDCARD_REST).and_then(|c| c.pattern.as_ref()) {
+                patterns.push(p.as_str());
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_none() && self.children.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NameTrie;
+
+    fn trie(patterns: &[&str]) -> NameTrie {
+        let mut trie = NameTrie::new();
+        for p in patterns {
+            trie.insert(p);
+        }
+        trie
+    }
+
+    fn sorted(mut patterns: Vec<&str>) -> Vec<&str> {
+        patterns.sort();
+        patterns
+    }
+
+    #[test]
+    fn exact_names() {
+        let trie = trie(&["build.output", "build"]);
+        assert_eq!(trie.matches("build.output"), vec!["build.output"]);
+        assert_eq!(trie.matches("build"), vec!["build"]);
+        assert!(trie.matches("build.output.x").is_empty());
+        assert!(trie.matches("other").is_empty());
+    }
+
+    #[test]
+    fn single_segment_wildcard() {
+        let trie = trie(&["build.*.output", "*"]);
+        assert_eq!(trie.matches("build.linux.output"), vec!["build.*.output"]);
+        assert!(trie.matches("build.output").is_empty());
+        assert!(trie.matches("build.linux.arm.output").is_empty());
+        assert_eq!(trie.matches("build"), vec!["*"]);
+    }
+
+    #[test]
+    fn rest_wildcard() {
+        let trie = trie(&["docker.>", ">"]);
+        assert_eq!(sorted(trie.matches("docker.start")), vec![">", "docker.>"]);
+        assert_eq!(sorted(trie.matches("docker.container.start")), vec![">", "docker.>"]);
+        // ">" is matching one or more segments, so it's not matching the prefix itself
+        assert_eq!(trie.matches("docker"), vec![">"]);
+    }
+
+    #[test]
+    fn all_matching_patterns() {
+        let trie = trie(&["a.b.c", "a.*.c", "a.>", "*.b.*"]);
+        assert_eq!(sorted(trie.matches("a.b.c")), vec!["*.b.*", "a.*.c", "a.>", "a.b.c"]);
+        assert_eq!(sorted(trie.matches("a.x.c")), vec!["a.*.c", "a.>"]);
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut trie = trie(&["a.*"]);
+        assert!(!trie.insert("a.*"));
+        assert!(trie.remove("a.*"));
+        assert!(!trie.remove("a.*"));
+        assert!(trie.matches("a.b").is_empty());
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    fn valid_patterns() {
+        assert!(NameTrie::is_valid("a.b"));
+        assert!(NameTrie::is_valid("a.*.c"));
+        assert!(NameTrie::is_valid("a.>"));
+        assert!(!NameTrie::is_valid("a.>.c"));
+    }
+}
@@ -0,0 +1,17 @@
+mod helper;
+mod network;
+mod graph;
+mod api;
+mod queue;
+mod auth;
+pub mod node;
+pub mod event;
+pub mod config;
+pub mod error;
+pub mod client;
+
+pub use node::{Node, NodeBuilder, NodeHandle};
+pub use event::{Event, EventHandler};
+pub use config::NodeConfig;
+pub use error::Error;
+pub use client::{Client, ClientBuilder};
@@ -1,12 +1,12 @@
-mod helper;
-mod node;
-mod event;
-mod network;
-mod config;
-mod graph;
+extern crate treescale;
 
-use node::Node;
+use treescale::{config, NodeBuilder};
+
+use std::process;
 
 fn main() {
-    Node::new(&config::parse_args()).start();
-}
\ No newline at end of file
+    if let Err(e) = config::parse_args().and_then(|c| NodeBuilder::from_config(c).run()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
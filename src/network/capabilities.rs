@@ -0,0 +1,210 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::cmp;
+
+use config::NetworkingConfig;
+use event::{Compression, EVENT_VERSION_LEGACY, EVENT_VERSION_HEADER};
+use helper::NetHelper;
+use error::Error;
+
+/// Event codecs which could be used by API clients
+pub const CAP_CODECS: &str = "codecs";
+/// Compression algorithms for event payload
+pub const CAP_COMPRESSION: &str = "compression";
+/// "1" if ping/pong heartbeat frames are supported
+pub const CAP_HEARTBEAT: &str = "heartbeat";
+/// Max event frame size in bytes which could be received
+pub const CAP_MAX_FRAME: &str = "max_frame";
+/// Authentication methods for Node connections
+pub const CAP_AUTH: &str = "auth";
+/// Event encoding versions, newer ones are having event header
+pub const CAP_EVENT_VERSION: &str = "event_version";
+/// "1" if event subscriptions could have "*" and ">" wildcards
+pub const CAP_WILDCARDS: &str = "wildcards";
+
+/// Protocol features advertised by Node or API client during handshake
+/// Every feature is a name with a comma separated list of values,
+/// so that agreed features are just an intersection of the lists from both sides
+/// Unknown features are ignored, which is allowing to add new ones without upgrading whole tree
+#[derive(Clone, Default)]
+pub struct Capabilities {
+    features: BTreeMap<String, String>
+}
+
+impl Capabilities {
+    pub fn new() -> Capabilities {
+        Capabilities {
+            features: BTreeMap::new()
+        }
+    }
+
+    /// Making capabilities supported by current Node
+    /// Heartbeat is supported even if it's disabled for us, because we are always answering to pings
+    pub fn local(config: &NetworkingConfig, node_auth: bool) -> Capabilities {
+        let mut caps = Capabilities::new();
+        caps.set(CAP_CODECS, "binary,json");
+        let compression: Vec<&str> = config.compression.split(',')
+                                        .map(|c| c.trim())
+                                        .filter(|c| Compression::from_name(c).is_some())
+                                        .collect();
+        caps.set(CAP_COMPRESSION, compression.join(",").as_str());
+        caps.set(CAP_HEARTBEAT, "1");
+        caps.set(CAP_MAX_FRAME, config.max_event_frame.to_string().as_str());
+        caps.set(CAP_AUTH, if node_auth { "challenge" } else { "" });
+        caps.set(CAP_EVENT_VERSION, format!("{},{}", EVENT_VERSION_HEADER, EVENT_VERSION_LEGACY).as_str());
+        caps.set(CAP_WILDCARDS, "1");
+        caps
+    }
+
+    #[inline(always)]
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.features.insert(String::from(name), String::from(value));
+    }
+
+    #[inline(always)]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.features.get(name).map(|v| v.as_str())
+    }
+
+    /// Getting list of values for given feature
+    pub fn list(&self, name: &str) -> Vec<&str> {
+        match self.get(name) {
+            Some(v) => v.split(',').filter(|s| !s.is_empty()).collect(),
+            None => vec![]
+        }
+    }
+
+    /// Checking if given value is in the list of given feature
+    #[inline(always)]
+    pub fn supports(&self, name: &str, value: &str) -> bool {
+        self.list(name).contains(&value)
+    }
+
+    /// Getting numeric feature, like max frame size
+    #[inline(always)]
+    pub fn number(&self, name: &str) -> Option<u64> {
+        self.get(name).and_then(|v| v.parse::<u64>().ok())
+    }
+
+    /// Making capabilities which are supported by both sides
+    /// Lists are intersected keeping our order of preference, numbers are taking minimum of both
+    pub fn intersect(&self, other: &Capabilities) -> Capabilities {
+        let mut caps = Capabilities::new();
+        for name in self.features.keys() {
+            if !other.features.contains_key(name) {
+                continue;
+            }
+
+            if name == CAP_MAX_FRAME {
+                if let (Some(a), Some(b)) = (self.number(name), other.number(name)) {
+                    caps.set(name, cmp::min(a, b).to_string().as_str());
+                }
+                continue;
+            }
+
+            let theirs = other.list(name);
+            let common: Vec<&str> = self.list(name).into_iter().filter(|v| theirs.contains(v)).collect();
+            caps.set(name, common.join(",").as_str());
+        }
+
+        caps
+    }
+
+    /// Encoding capabilities as a length prefixed name and value pairs
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let total = self.features.iter().fold(0, |n, (k, v)| n + 8 + k.len() + v.len());
+        let mut buffer = vec![0; total];
+        let mut offset = 0;
+        for (name, value) in &self.features {
+            for field in &[name, value] {
+                offset += NetHelper::u32_to_bytes(field.len() as u32, &mut buffer, offset);
+                buffer[offset..offset + field.len()].copy_from_slice(field.as_bytes());
+                offset += field.len();
+            }
+        }
+
+        buffer
+    }
+
+    /// Parsing capabilities from bytes
+    pub fn from_bytes(data: &[u8]) -> Result<Capabilities, Error> {
+        let data = data.to_vec();
+        let mut caps = Capabilities::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let mut pair = vec![];
+            for _ in 0..2 {
+                let len = match NetHelper::bytes_to_u32(&data, offset) {
+                    Ok(l) => l as usize,
+                    Err(e) => return Err(Error::Handshake(format!("Invalid capabilities frame -> {}", e)))
+                };
+
+                if offset + 4 + len > data.len() {
+                    return Err(Error::Handshake(String::from("Capabilities field is out of data bounds")));
+                }
+
+                match String::from_utf8(data[offset + 4..offset + 4 + len].to_vec()) {
+                    Ok(s) => pair.push(s),
+                    Err(_) => return Err(Error::Handshake(String::from("Capabilities field is not valid UTF-8")))
+                }
+                offset += 4 + len;
+            }
+
+            caps.set(&pair[0], &pair[1]);
+        }
+
+        Ok(caps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intersect_keeps_common_values_in_our_order() {
+        let mut ours = Capabilities::new();
+        ours.set(CAP_CODECS, "binary,json");
+        ours.set(CAP_COMPRESSION, "zstd,lz4");
+        ours.set(CAP_MAX_FRAME, "1000");
+        ours.set(CAP_AUTH, "challenge");
+
+        let mut theirs = Capabilities::new();
+        theirs.set(CAP_CODECS, "json");
+        theirs.set(CAP_COMPRESSION, "lz4,zstd");
+        theirs.set(CAP_MAX_FRAME, "500");
+        theirs.set("unknown", "1");
+
+        let agreed = ours.intersect(&theirs);
+        assert_eq!(agreed.list(CAP_CODECS), vec!["json"]);
+        assert_eq!(agreed.list(CAP_COMPRESSION), vec!["zstd", "lz4"]);
+        assert_eq!(agreed.number(CAP_MAX_FRAME), Some(500));
+        assert_eq!(agreed.get(CAP_AUTH), None);
+        assert_eq!(agreed.get("unknown"), None);
+    }
+
+    #[test]
+    fn intersect_without_common_values() {
+        let mut ours = Capabilities::new();
+        ours.set(CAP_COMPRESSION, "zstd");
+        let mut theirs = Capabilities::new();
+        theirs.set(CAP_COMPRESSION, "lz4");
+
+        let agreed = ours.intersect(&theirs);
+        assert!(agreed.list(CAP_COMPRESSION).is_empty());
+        assert!(!agreed.supports(CAP_COMPRESSION, "zstd"));
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut caps = Capabilities::new();
+        caps.set(CAP_HEARTBEAT, "1");
+        caps.set(CAP_EVENT_VERSION, "2,1");
+
+        let parsed = Capabilities::from_bytes(&caps.to_bytes()).unwrap();
+        assert_eq!(parsed.get(CAP_HEARTBEAT), Some("1"));
+        assert_eq!(parsed.list(CAP_EVENT_VERSION), vec!["2", "1"]);
+        assert!(Capabilities::from_bytes(&caps.to_bytes()[..5]).is_err());
+    }
+}
@@ -4,18 +4,35 @@ extern crate  mio;
 use self::mio::Token;
 
 use config::MAX_API_VERSION;
+use event::Compression;
+
+/// API version is kept in lower 16 bits of handshake version field
+/// and event codec requested by API client is in the next 8 bits
+pub const API_VERSION_MASK: u32 = 0xFFFF;
+pub const API_CODEC_SHIFT: u32 = 16;
+
+/// First API version, without capabilities exchange
+pub const LEGACY_API_VERSION: u32 = 1;
+/// Starting from this API version both sides are exchanging capabilities after handshake
+pub const CAPABILITIES_API_VERSION: u32 = 2;
 
 #[derive(Clone)]
 pub enum SocketType {
-    NONE,
-    TCP,
+    None,
+    Tcp,
+    Unix,
+    WebSocket,
 }
 
 #[derive(Clone)]
 pub struct ConnectionIdentity {
     pub handler_index: usize,
     pub socket_type: SocketType,
-    pub socket_token: Token
+    pub socket_token: Token,
+    // compression for event data agreed with other Node
+    pub compression: Compression,
+    // event encoding version agreed with other side
+    pub event_version: u8
 }
 
 pub struct Connection {
@@ -41,8 +58,8 @@ impl Connection {
     #[inline(always)]
     pub fn new(token: String, value: u64, identity: ConnectionIdentity) -> Connection {
         Connection {
-            token: token,
-            value: value,
+            token,
+            value,
             identities: vec![identity],
             identity_index: 0
         }
@@ -78,6 +95,12 @@ impl Connection {
         self.identities[i].clone()
     }
 
+    /// Splitting handshake version field into API version and requested event codec
+    #[inline(always)]
+    pub fn split_api_version(version: u32) -> (u32, u8) {
+        (version & API_VERSION_MASK, (version >> API_CODEC_SHIFT) as u8)
+    }
+
     /// Checking API version, if it's not correct function will return false
     #[inline(always)]
     pub fn check_api_version(version: u32) -> bool {
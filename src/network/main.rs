@@ -4,18 +4,19 @@ extern crate mio;
 use self::mio::{Ready, PollOpt, Token};
 
 use node::{Node, NET_RECEIVER_CHANNEL_TOKEN};
-use network::{ConnectionIdentity, Connection, TcpNetwork, SocketType, TcpHandlerCommand, TcpHandlerCMD};
+use network::{ConnectionIdentity, Connection, TcpNetwork, UnixNetwork, WsNetwork, SocketType, TcpHandlerCommand, TcpHandlerCMD};
 use helper::{Log, NetHelper};
-use event::{Event};
+use event::{Event, Compression};
+use api::ApiDelivery;
+use error::Error;
 
-use std::error::Error;
-use std::process;
 use std::sync::Arc;
 use std::collections::btree_map::Entry::{Occupied, Vacant};
 
 pub enum NetworkCMD {
     None,
     ConnectionClose,
+    ConnectionFailed,
     HandleConnection,
     HandleEvent
 }
@@ -25,12 +26,14 @@ pub struct NetworkCommand {
     pub token: Vec<String>,
     pub value: Vec<u64>,
     pub conn_identity: Vec<ConnectionIdentity>,
-    pub event: Vec<Event>
+    pub event: Vec<Event>,
+    // addresses of connections made by current Node
+    pub address: Vec<String>
 }
 
 pub trait Networking {
     /// Main function to init Networking
-    fn init_networking(&mut self);
+    fn init_networking(&mut self) -> Result<(), Error>;
 
     /// Handle Networking channel events as a NetworkCommand
     fn notify(&mut self, command: &mut NetworkCommand);
@@ -43,6 +46,38 @@ pub trait Networking {
 
     /// sending event with specific path
     fn emit(&mut self, event: Event);
+
+    /// sending event directly to connections with given tokens
+    /// without looking to event path, this is mainly for API connections
+    fn send_to(&mut self, tokens: &[String], event: &Event);
+
+    /// encoding event once for every group of connections and writing it to them
+    fn write_encoded(&self, event: &Event, groups: EncodingGroups);
+
+    /// writing data to given sockets grouped by TCP handler index
+    fn write_to_handlers(&self, tcp_conns_to_send: Vec<Vec<Token>>, data: Arc<Vec<u8>>);
+}
+
+/// Sockets grouped by event encoding version and compression, and then by TCP handler index
+type EncodingGroups = Vec<((u8, Compression), Vec<Vec<Token>>)>;
+
+/// Adding connection identity to the group of its event encoding
+#[inline(always)]
+fn add_to_group(groups: &mut EncodingGroups, handlers_count: usize, encoding: (u8, Compression), identity: &ConnectionIdentity) {
+    match identity.socket_type {
+        SocketType::Tcp | SocketType::Unix | SocketType::WebSocket => {
+            let i = match groups.iter().position(|g| g.0 == encoding) {
+                Some(i) => i,
+                None => {
+                    groups.push((encoding, vec![Vec::new(); handlers_count]));
+                    groups.len() - 1
+                }
+            };
+            groups[i].1[identity.handler_index].push(identity.socket_token);
+        }
+
+        SocketType::None => {}
+    }
 }
 
 
@@ -54,7 +89,8 @@ impl NetworkCommand {
             token: vec![],
             value: vec![],
             conn_identity: vec![],
-            event: vec![]
+            event: vec![],
+            address: vec![]
         }
     }
 }
@@ -75,6 +111,7 @@ impl Networking for Node {
                 let token = command.token.remove(0);
                 let identity = command.conn_identity.remove(0);
                 let value = command.value.remove(0);
+                let address = if command.address.is_empty() { String::new() } else { command.address.remove(0) };
 
                 let contained_token = match self.connections.entry(token.clone()) {
                     Vacant(entry) => {
@@ -97,7 +134,7 @@ impl Networking for Node {
                     if value == 0 {
                         self.on_new_api_connection(&token);
                     } else { // if we have regular Node connection
-                        self.on_new_connection(&token, value);
+                        self.on_new_connection(&token, value, &address);
                     }
                 }
             }
@@ -130,6 +167,15 @@ impl Networking for Node {
                 }
             }
 
+            NetworkCMD::ConnectionFailed => {
+                if command.address.len() != 1 {
+                    return;
+                }
+
+                let address = command.address.remove(0);
+                self.on_connection_failed(&address);
+            }
+
             NetworkCMD::HandleEvent => {
                 // currently supporting only one connection per single command request
                 if command.token.len() != 1 {
@@ -138,15 +184,69 @@ impl Networking for Node {
 
                 // getting token out
                 let token = command.token.remove(0);
+                let from_api = match self.connections.get(&token) {
+                    Some(conn) => conn.value == 0,
+                    None => false
+                };
 
                 while !command.event.is_empty() {
-                    let event = command.event.remove(0);
+                    let mut event = command.event.remove(0);
+                    // API clients are sending only event name or target
+                    // so we need to calculate path for them
+                    if from_api && event.path.is_zero() {
+                        event.path = self.resolve_path(&event);
+                    }
+
+                    // event is getting ID and hop limit when it enters into the tree,
+                    // and counting hops while it's transferred between Nodes
+                    // API clients could set lower hop limit for their events, 0 is the same as unset
+                    // but ID is always ours, otherwise API client could fill recent events with IDs of other events
+                    // Events coming from API version 1 Nodes don't have ID and hop limit, so they are stamped again here,
+                    // which means duplicates and hop limit are checked only between Nodes supporting event header
+                    if from_api {
+                        event.id = 0;
+                        if event.ttl == Some(0) {
+                            event.ttl = None;
+                        }
+                    }
+                    event.stamp(self.net_config.max_hops);
+                    if !from_api {
+                        event.hops = event.hops.saturating_add(1);
+                    }
+
+                    // event which we already handled is coming back because of routing loop
+                    if !self.net_recent_events.insert(event.id) {
+                        self.net_dropped_events.duplicate += 1;
+                        Log::warn("Dropping duplicate event", format!("{} -> {:016x}", event.name, event.id).as_str());
+                        continue;
+                    }
+
                     // if event processing passing fine
                     // emitting event based on his path
-                    if self.on_event_data(&token, &event) && !event.path.is_zero() {
-                        // then trying to send event over available connections
-                        self.emit(event);
+                    if !self.on_event_data(&token, &event) {
+                        continue;
+                    }
+
+                    // if path contains our value, then event is for our API clients
+                    if event.path.dividable(self.value) {
+                        event.path.div(self.value);
+                        self.deliver_local(&event);
+                    }
+
+                    if event.path.is_zero() {
+                        continue;
                     }
+
+                    // event is not forwarded anymore if it reached its hop limit
+                    if event.ttl == Some(0) {
+                        self.net_dropped_events.hop_limit += 1;
+                        Log::warn("Dropping event which reached its hop limit", format!("{} -> {:016x}", event.name, event.id).as_str());
+                        continue;
+                    }
+
+                    // then trying to send event over available connections
+                    event.ttl = event.ttl.map(|ttl| ttl - 1);
+                    self.emit(event);
                 }
             }
 
@@ -154,21 +254,18 @@ impl Networking for Node {
         }
     }
 
-    fn init_networking(&mut self) {
+    fn init_networking(&mut self) -> Result<(), Error> {
         // Registering Networking receiver
-        match self.poll.register(&self.net_receiver_chan
-                                 , NET_RECEIVER_CHANNEL_TOKEN
-                                 , Ready::readable()
-                                 , PollOpt::level()) {
-            Ok(_) => {},
-            Err(e) => {
-                Log::error("Unable to register networking receiver channel to Node POLL service"
-                           , e.description());
-                process::exit(1);
-            }
+        if let Err(e) = self.poll.register(&self.net_receiver_chan
+                                           , NET_RECEIVER_CHANNEL_TOKEN
+                                           , Ready::readable()
+                                           , PollOpt::level()) {
+            return Err(Error::io("Unable to register networking receiver channel to Node POLL service", e));
         }
 
-        self.register_tcp();
+        self.register_tcp()?;
+        self.register_unix()?;
+        self.register_ws()
     }
 
     #[inline(always)]
@@ -185,6 +282,8 @@ impl Networking for Node {
         }
 
         self.tcp_ready(token, event_kind)
+            || self.unix_ready(token, event_kind)
+            || self.ws_ready(token, event_kind)
     }
 
     #[inline(always)]
@@ -195,7 +294,7 @@ impl Networking for Node {
         // 4 Bytes token string length
         // N bytes for token string
         // 8 bytes for Prime Value
-        let mut buffer = vec![0; (4 + 4 + token_len + 8)];
+        let mut buffer = vec![0; 4 + 4 + token_len + 8];
         let mut offset = NetHelper::u32_to_bytes(self.api_version, &mut buffer, 0);
         offset += NetHelper::u32_to_bytes(total_value_len as u32, &mut buffer, offset);
         buffer[offset..offset + token_len].copy_from_slice(self.token.as_bytes());
@@ -205,11 +304,18 @@ impl Networking for Node {
     }
 
     #[inline(always)]
-    fn emit(&mut self, event: Event) {
-        let mut tcp_conns_to_send: Vec<Vec<Token>> = vec![Vec::new(); self.net_tcp_handler_sender_chan.len()];
-        let mut event = event;
-        for (_, mut conn) in &mut self.connections {
-            if conn.value == 0 {
+    fn emit(&mut self, mut event: Event) {
+        // events made by current Node are getting their ID here
+        event.stamp(self.net_config.max_hops);
+
+        // connections are grouped by encoding, so that event is serialized
+        // and compressed only once for every algorithm, not for every connection
+        let threshold = self.net_config.compress_threshold;
+        let compress = threshold > 0 && event.data.len() > threshold;
+        let handlers_count = self.net_tcp_handler_sender_chan.len();
+        let mut groups: EncodingGroups = vec![];
+        for conn in self.connections.values_mut() {
+            if conn.value == 0 || conn.identity_count() == 0 {
                 continue;
             }
 
@@ -217,41 +323,68 @@ impl Networking for Node {
                 continue;
             }
 
-            // if we trying to send to this connection
-            // removing it from path
-            event.path.div(conn.value);
+            // keeping connection value inside path, receiver Node will remove it
+            // after delivering event to his API clients
             let identity = conn.get_identity();
-            match identity.socket_type {
-                SocketType::TCP => {
-                    tcp_conns_to_send[identity.handler_index].push(identity.socket_token);
-                }
+            let compression = if compress { identity.compression } else { Compression::None };
+            add_to_group(&mut groups, handlers_count, (identity.event_version, compression), &identity);
+        }
 
-                SocketType::NONE => {}
+        self.write_encoded(&event, groups);
+    }
+
+    #[inline(always)]
+    fn send_to(&mut self, tokens: &[String], event: &Event) {
+        let handlers_count = self.net_tcp_handler_sender_chan.len();
+        let mut groups: EncodingGroups = vec![];
+        for token in tokens {
+            let conn = match self.connections.get_mut(token) {
+                Some(c) => c,
+                None => continue
+            };
+
+            if conn.identity_count() == 0 {
+                continue;
             }
-        }
 
-        if tcp_conns_to_send.len() == 0 {
-            return;
+            let identity = conn.get_identity();
+            add_to_group(&mut groups, handlers_count, (identity.event_version, Compression::None), &identity);
         }
 
-        let data = Arc::new(match event.to_raw() {
-            Some(d) => d,
-            None => return
-        });
+        self.write_encoded(event, groups);
+    }
 
-        for i in 0..self.net_tcp_handler_sender_chan.len() {
-            if tcp_conns_to_send[i].len() == 0 {
+    #[inline(always)]
+    fn write_encoded(&self, event: &Event, groups: EncodingGroups) {
+        for ((version, compression), tcp_conns_to_send) in groups {
+            // other groups could still be encoded, so skipping only this one
+            let data = Arc::new(match event.to_raw_with(version, compression) {
+                Some(d) => d,
+                None => {
+                    Log::error("Unable to encode event", format!("{} -> version {}, compression {}", event.name, version, compression.name()).as_str());
+                    continue;
+                }
+            });
+
+            self.write_to_handlers(tcp_conns_to_send, data);
+        }
+    }
+
+    #[inline(always)]
+    fn write_to_handlers(&self, tcp_conns_to_send: Vec<Vec<Token>>, data: Arc<Vec<u8>>) {
+        for (sender, tokens) in self.net_tcp_handler_sender_chan.iter().zip(tcp_conns_to_send) {
+            if tokens.is_empty() {
                 continue;
             }
 
             let mut command = TcpHandlerCommand::new();
             command.cmd = TcpHandlerCMD::WriteData;
-            command.token = tcp_conns_to_send[i].clone();
+            command.token = tokens;
             command.data = vec![data.clone()];
-            match self.net_tcp_handler_sender_chan[i].send(command) {
+            match sender.send(command) {
                 Ok(_) => {},
                 Err(e) => {
-                    Log::error("Unable to send data to TcpHandler during emiting event", e.description());
+                    Log::error("Unable to send data to TcpHandler during emiting event", e.to_string().as_str());
                 }
             }
         }
@@ -1,12 +1,22 @@
 #![allow(dead_code)]
 mod main;
 mod tcp;
+mod unix;
+mod ws;
 mod conn;
+mod stream;
+mod capabilities;
 
 pub use self::main::{Networking, NetworkCMD, NetworkCommand};
-pub use self::conn::{Connection, ConnectionIdentity, SocketType};
+pub use self::conn::{Connection, ConnectionIdentity, SocketType, API_CODEC_SHIFT
+                     , LEGACY_API_VERSION, CAPABILITIES_API_VERSION};
 pub use self::tcp::{TcpNetwork
                     , TcpHandlerCommand, TcpHandlerCMD, TcpHandler
-                    , Slab , TcpConnection};
+                    , Slab , TcpConnection, TcpTls
+                    , TCP_FRAME_PING, TCP_FRAME_PONG, TCP_FRAME_CAPABILITIES};
+pub use self::unix::{UnixNetwork, UnixStream, UnixListener};
+pub use self::ws::{WsNetwork, WsStream};
+pub use self::stream::Stream;
+pub use self::capabilities::{Capabilities, CAP_CODECS, CAP_AUTH, CAP_HEARTBEAT, CAP_MAX_FRAME, CAP_COMPRESSION, CAP_EVENT_VERSION, CAP_WILDCARDS};
 
 pub const CONNECTION_COUNT_PRE_ALLOC: usize = 1024;
\ No newline at end of file
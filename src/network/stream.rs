@@ -0,0 +1,122 @@
+#![allow(dead_code)]
+extern crate mio;
+
+use self::mio::{Evented, Poll, Token, Ready, PollOpt};
+use self::mio::tcp::TcpStream;
+
+use network::{UnixStream, WsStream, SocketType};
+
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+
+/// Socket stream for connections handled by TCP handlers
+/// Unix sockets are using the same framing, so they are handled in the same way
+/// WebSocket stream is converting its messages to the same framing
+pub enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+    Ws(WsStream)
+}
+
+impl Stream {
+    #[inline(always)]
+    pub fn socket_type(&self) -> SocketType {
+        match *self {
+            Stream::Tcp(_) => SocketType::Tcp,
+            Stream::Unix(_) => SocketType::Unix,
+            Stream::Ws(_) => SocketType::WebSocket
+        }
+    }
+
+    /// Connection is coming from the same host
+    #[inline(always)]
+    pub fn is_local(&self) -> bool {
+        match *self {
+            Stream::Tcp(_) => false,
+            Stream::Unix(_) => true,
+            Stream::Ws(_) => false
+        }
+    }
+
+    /// WebSocket peers are answering to pings by the protocol itself,
+    /// so heartbeat is working for them without capabilities exchange
+    #[inline(always)]
+    pub fn native_heartbeat(&self) -> bool {
+        matches!(*self, Stream::Ws(_))
+    }
+
+    /// Stream is keeping some data which is not written to socket yet
+    #[inline(always)]
+    pub fn wants_write(&self) -> bool {
+        match *self {
+            Stream::Ws(ref s) => s.wants_write(),
+            _ => false
+        }
+    }
+
+    #[inline(always)]
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match *self {
+            Stream::Tcp(ref s) => s.shutdown(how),
+            Stream::Unix(ref s) => s.shutdown(how),
+            Stream::Ws(ref s) => s.shutdown(how)
+        }
+    }
+}
+
+impl Read for Stream {
+    #[inline(always)]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Tcp(ref mut s) => s.read(buf),
+            Stream::Unix(ref mut s) => s.read(buf),
+            Stream::Ws(ref mut s) => s.read(buf)
+        }
+    }
+}
+
+impl Write for Stream {
+    #[inline(always)]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Tcp(ref mut s) => s.write(buf),
+            Stream::Unix(ref mut s) => s.write(buf),
+            Stream::Ws(ref mut s) => s.write(buf)
+        }
+    }
+
+    #[inline(always)]
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Stream::Tcp(ref mut s) => s.flush(),
+            Stream::Unix(ref mut s) => s.flush(),
+            Stream::Ws(ref mut s) => s.flush()
+        }
+    }
+}
+
+impl Evented for Stream {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        match *self {
+            Stream::Tcp(ref s) => s.register(poll, token, interest, opts),
+            Stream::Unix(ref s) => s.register(poll, token, interest, opts),
+            Stream::Ws(ref s) => s.register(poll, token, interest, opts)
+        }
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        match *self {
+            Stream::Tcp(ref s) => s.reregister(poll, token, interest, opts),
+            Stream::Unix(ref s) => s.reregister(poll, token, interest, opts),
+            Stream::Ws(ref s) => s.reregister(poll, token, interest, opts)
+        }
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        match *self {
+            Stream::Tcp(ref s) => s.deregister(poll),
+            Stream::Unix(ref s) => s.deregister(poll),
+            Stream::Ws(ref s) => s.deregister(poll)
+        }
+    }
+}
@@ -1,16 +1,20 @@
 #![allow(dead_code)]
 extern crate mio;
+extern crate rustls;
 
 use std::sync::Arc;
 use std::collections::VecDeque;
-use std::io::{ErrorKind, Read, Write};
+use std::io::{self, ErrorKind, Read, Write};
 use std::net::Shutdown;
-use std::error::Error;
+use std::time::{Duration, Instant};
 
 use helper::{Log, NetHelper};
+use network::{Stream, Capabilities, CAP_MAX_FRAME, API_CODEC_SHIFT};
+use event::EventCodec;
+use error::Error;
 
 use self::mio::{Token, Poll, PollOpt, Ready};
-use self::mio::tcp::TcpStream;
+use self::rustls::Connection as TlsSession;
 
 /// Base TCP connection structure
 pub struct TcpConnection {
@@ -18,16 +22,51 @@ pub struct TcpConnection {
     pub api_version: u32,
 
     // Socket for handling connection
-    pub socket: TcpStream,
+    pub socket: Stream,
     pub socket_token: Token,
 
+    // TLS session, if it's enabled all data is going through it
+    pub tls: Option<TlsSession>,
+
     // this connection coming from server or client connection
     pub from_server: bool,
 
+    // address which we have been connected to, empty for connections accepted by server
+    pub address: String,
+
     // token for connection as an identification
     pub conn_token: String,
     pub conn_value: u64,
 
+    // event codec requested in handshake, and codec accepted for this connection
+    pub requested_codec: u8,
+    pub codec: EventCodec,
+
+    // other side is going to send its capabilities before connection is accepted
+    pub capabilities_expected: bool,
+    // capabilities supported by both sides, None for connections without capabilities exchange
+    pub capabilities: Option<Capabilities>,
+
+    // connection is rejected, and would be closed after writing everything from queue
+    pub closing: bool,
+
+    // connection passed handshake and Networking knows about it
+    pub accepted: bool,
+
+    // API key is checked by authentication worker, connection is not read until we get result
+    pub authenticating: bool,
+
+    // challenge sent to other Node during authentication
+    pub auth_challenge: Vec<u8>,
+    // challenge received from other Node, our response is made for both challenges
+    pub auth_peer_challenge: Vec<u8>,
+    // we already responded to the challenge from other Node
+    pub auth_responded: bool,
+    // other Node advertised challenge authentication, so we are waiting for its challenge
+    pub auth_expected: bool,
+    // response from other Node for our challenge is received, and it is valid or not
+    pub auth_verified: Option<bool>,
+
     // pending data information
     pending_data_len: usize,
     pending_data_index: usize,
@@ -42,43 +81,88 @@ pub struct TcpConnection {
     // and probably would be used from writer loop
     // data queue for writing it to connection
     writable: VecDeque<Arc<Vec<u8>>>,
+    // our handshake for connections accepted by server, it's queued only after codec is accepted
+    // otherwise TLS session could send it before codec is added
+    handshake: Option<Arc<Vec<u8>>>,
     // index for current partial data to write
     writable_data_index: usize,
+
+    // last time when we got something from this connection
+    last_read: Instant,
 }
 
 impl TcpConnection {
     /// Making new TCP connection from accepted socket
     #[inline(always)]
-    pub fn new(socket: TcpStream, token: Token, from_server: bool) -> TcpConnection {
+    pub fn new(socket: Stream, token: Token, from_server: bool) -> TcpConnection {
         TcpConnection {
             api_version: 0,
-            socket: socket,
+            socket,
             socket_token: token,
-            from_server: from_server,
+            tls: None,
+            from_server,
+            address: String::new(),
             conn_token: String::default(),
             conn_value: 0,
+            requested_codec: 0,
+            codec: EventCodec::Binary,
+            capabilities_expected: false,
+            capabilities: None,
+            closing: false,
+            accepted: false,
+            authenticating: false,
+            auth_challenge: vec![],
+            auth_peer_challenge: vec![],
+            auth_responded: false,
+            auth_expected: false,
+            auth_verified: None,
             pending_data_len: 0,
             pending_data_index: 0,
             pending_data: vec![],
             pending_endian: vec![0; 4],
             pending_endian_index: 0,
             writable: VecDeque::new(),
-            writable_data_index: 0
+            handshake: None,
+            writable_data_index: 0,
+            last_read: Instant::now()
         }
     }
 
+    /// Marking connection as alive, because we got something from it
+    #[inline(always)]
+    pub fn touch(&mut self) {
+        self.last_read = Instant::now();
+    }
+
+    /// Getting time passed from the last data received from this connection
+    #[inline(always)]
+    pub fn idle(&self) -> Duration {
+        self.last_read.elapsed()
+    }
+
     #[inline(always)]
     pub fn add_writable_data(&mut self, data: Arc<Vec<u8>>) {
         self.writable.push_back(data);
     }
 
+    /// Adding our handshake information for writing it to other side
+    /// For connections accepted by server it's written after we know which codec other side requested
+    #[inline(always)]
+    pub fn add_handshake(&mut self, data: Arc<Vec<u8>>) {
+        if self.from_server {
+            self.handshake = Some(data);
+        } else {
+            self.writable.push_back(data);
+        }
+    }
+
     /// Registering connection to give POLL service
     #[inline(always)]
     pub fn register(&self, poll: &Poll) -> bool {
         match poll.register(&self.socket, self.socket_token, Ready::readable(), PollOpt::edge()) {
             Ok(_) => {}
             Err(e) => {
-                Log::error("Unable to register tcp connection to given poll service", e.description());
+                Log::error("Unable to register tcp connection to given poll service", e.to_string().as_str());
                 return false;
             }
         }
@@ -92,7 +176,7 @@ impl TcpConnection {
         match poll.reregister(&self.socket, self.socket_token, Ready::readable(), PollOpt::edge()) {
             Ok(_) => {}
             Err(e) => {
-                Log::error("Unable to make tcp connection readable for given poll service", e.description());
+                Log::error("Unable to make tcp connection readable for given poll service", e.to_string().as_str());
                 return false;
             }
         }
@@ -106,7 +190,7 @@ impl TcpConnection {
         match poll.reregister(&self.socket, self.socket_token, Ready::writable(), PollOpt::edge()) {
             Ok(_) => {}
             Err(e) => {
-                Log::error("Unable to make tcp connection writable for given poll service", e.description());
+                Log::error("Unable to make tcp connection writable for given poll service", e.to_string().as_str());
                 return false;
             }
         }
@@ -115,44 +199,89 @@ impl TcpConnection {
 
     /// Reading Endian number using Networking API
     #[inline(always)]
-    pub fn read_endian(&mut self) -> Option<(bool, u32)> {
-        let read_len = match self.socket.read(&mut self.pending_endian[self.pending_endian_index..]) {
-            Ok(n) => if n == 0 { return None } else { n },
+    pub fn read_endian(&mut self) -> Result<(bool, u32), Error> {
+        let read_len = match TcpConnection::read_socket(&mut self.socket, &mut self.tls, &mut self.pending_endian[self.pending_endian_index..]) {
+            Ok(n) => if n == 0 { return Err(Error::closed()) } else { n },
             Err(e) => {
                 // if we got WouldBlock, then this is Non Blocking socket
                 // and data still not available for this, so it's not a connection error
                 if e.kind() == ErrorKind::WouldBlock {
-                    return Some((false, 0))
+                    return Ok((false, 0))
                 }
 
-                return None;
+                return Err(Error::io("Unable to read from connection", e));
             }
         };
 
         // if we got data less than we expected
         if read_len + self.pending_endian_index < 4 {
             self.pending_endian_index += read_len;
-            return Some((false, 0));
+            return Ok((false, 0));
         }
 
-        let (parsed, number) = NetHelper::bytes_to_u32(&self.pending_endian, 0);
         // if we are unable to parse given BigEndian
         // then something wrong with connection or API, we should close it
-        if !parsed {
-            return None;
-        }
+        let number = NetHelper::bytes_to_u32(&self.pending_endian, 0)?;
 
         // resting index for next time read
         self.pending_endian_index = 0;
 
-        Some((true, number))
+        Ok((true, number))
+    }
+
+    /// Reading data from socket, or decrypted data from TLS session if it's enabled
+    /// Returns WouldBlock error if there is no data available right now
+    #[inline(always)]
+    fn read_socket(socket: &mut Stream, tls: &mut Option<TlsSession>, buffer: &mut [u8]) -> io::Result<usize> {
+        let session = match *tls {
+            Some(ref mut s) => s,
+            None => return socket.read(buffer)
+        };
+
+        loop {
+            // giving data which is already decrypted
+            match session.reader().read(buffer) {
+                Ok(n) => return Ok(n),
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e)
+            }
+
+            // reading more TLS records from socket
+            if session.read_tls(socket)? == 0 {
+                return Ok(0);
+            }
+
+            if let Err(e) = session.process_new_packets() {
+                // letting other side know why we are closing connection
+                let _ = session.write_tls(socket);
+                return Err(io::Error::new(ErrorKind::InvalidData, e));
+            }
+
+            // TLS handshake messages should be sent right away
+            while session.wants_write() {
+                match session.write_tls(socket) {
+                    Ok(_) => {}
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => return Err(e)
+                }
+            }
+        }
+    }
+
+    /// TLS session have some data which is not written to socket yet
+    #[inline(always)]
+    pub fn wants_write(&self) -> bool {
+        match self.tls {
+            Some(ref s) => s.wants_write(),
+            None => self.socket.wants_write()
+        }
     }
 
     /// Reading API version as a big endian as a first handshake between connections
     /// Will return (False, N) if there is not enough data to parse
-    /// Will return None if there is some problem with connection and we need to close it
+    /// Will return error if there is some problem with connection and we need to close it
     #[inline(always)]
-    pub fn read_api_version(&mut self) -> Option<(bool, u32)> {
+    pub fn read_api_version(&mut self) -> Result<(bool, u32), Error> {
         // API version is actually a BigEndian u32
         // so just reading as a big endian number
         self.read_endian()
@@ -160,18 +289,15 @@ impl TcpConnection {
 
     /// Reading connection Token and Prime Value combination as a second phase of handshake
     /// Will return (false, Token, N) if there is not enough data to parse
-    /// Will return None if there is connection error and we need to close it
+    /// Will return error if there is connection error and we need to close it
     #[inline(always)]
-    pub fn read_token_value(&mut self) -> Option<(bool, String, u64)> {
+    pub fn read_token_value(&mut self, max_len: usize) -> Result<(bool, String, u64), Error> {
         // reading BigEndian length of token
-        let (done, data) = match self.read_data_once() {
-            Some((d, b)) => (d, b),
-            None => return None
-        };
+        let (done, data) = self.read_data_once(max_len)?;
 
         // if we still don't have enough data, returning and waiting to a new cycle
         if !done {
-            return Some((false, String::default(), 0))
+            return Ok((false, String::default(), 0))
         }
 
         // our data contains Token and Value
@@ -179,45 +305,41 @@ impl TcpConnection {
         // so len() - 8 should be text length
         if data.len() <= 8 {
             // if we got wrong API closing connection
-            return None;
+            return Err(Error::Handshake(format!("Token and Value field is too short, {} bytes", data.len())));
         }
 
         let text_len = data.len() - 8;
 
         // Converting our token to string
-        let token =  match String::from_utf8(Vec::from(&data[..text_len])) {
+        let token = match String::from_utf8(Vec::from(&data[..text_len])) {
             Ok(t) => t,
-            Err(e) => {
-                Log::error("Unable to convert received Token bytes to string", e.description());
-                return None;
-            }
+            Err(e) => return Err(Error::Handshake(format!("Unable to convert received Token bytes to string -> {}", e)))
         };
 
         // converting Value bytes to u64
         // if not converted just closing connection, because it is wrong or corrupted API data
-        let (converted, value) = NetHelper::bytes_to_u64(&data, text_len);
-        if !converted {
-            return None;
-        }
+        let value = NetHelper::bytes_to_u64(&data, text_len)?;
 
-        Some((true, token, value))
+        Ok((true, token, value))
     }
 
     /// Reading only one part of data which means that only one
     /// Byte chunk would be returned
     /// This is the base function to read data from socket
+    /// Frames longer than "max_len" are rejected, to not allocate memory for them
     #[inline(always)]
-    pub fn read_data_once(&mut self) -> Option<(bool, Vec<u8>)> {
+    pub fn read_data_once(&mut self, max_len: usize) -> Result<(bool, Vec<u8>), Error> {
         // fist of all getting BigEndian number to determine how many bytes we need to read
         if self.pending_data_len == 0 {
-            let (done_endian, data_len) = match self.read_endian() {
-                Some(d) => d,
-                None => return None
-            };
+            let (done_endian, data_len) = self.read_endian()?;
 
             // returning if we need more data
             if !done_endian {
-                return Some((false, vec![]));
+                return Ok((false, vec![]));
+            }
+
+            if data_len as usize > max_len {
+                return Err(Error::Capacity(format!("Frame is too large, {} bytes, max allowed {} bytes", data_len, max_len)));
             }
 
             // making data with specific length
@@ -227,42 +349,39 @@ impl TcpConnection {
 
         // if we got here then we have defined pending_data and total length
         // so we need to read data until pending_data_index is equal to length
-        let read_len = match self.socket.read(&mut self.pending_data[0][self.pending_data_index..]) {
-            Ok(n) => if n == 0 { return None } else { n },
+        let read_len = match TcpConnection::read_socket(&mut self.socket, &mut self.tls, &mut self.pending_data[0][self.pending_data_index..]) {
+            Ok(n) => if n == 0 { return Err(Error::closed()) } else { n },
             Err(e) => {
                 // if we got WouldBlock, then this is Non Blocking socket
                 // and data still not available for this, so it's not a connection error
                 if e.kind() == ErrorKind::WouldBlock {
-                    return Some((false, vec![]))
+                    return Ok((false, vec![]))
                 }
 
-                return None;
+                return Err(Error::io("Unable to read from connection", e));
             }
         };
 
         if self.pending_data_index + read_len < self.pending_data_len {
             self.pending_data_index += read_len;
-            return Some((false, vec![]))
+            return Ok((false, vec![]))
         }
 
         // resetting values
         self.pending_data_index = 0;
         self.pending_data_len = 0;
 
-        Some((true, self.pending_data.remove(0)))
+        Ok((true, self.pending_data.remove(0)))
     }
 
     /// Reading all data available in socket
     /// so this will return only if read_once function will send (false, vec![])
     /// This will help to get all data once and then consume it using single event
     #[inline(always)]
-    pub fn read_data(&mut self) -> Option<Vec<Vec<u8>>> {
+    pub fn read_data(&mut self, max_len: usize) -> Result<Vec<Vec<u8>>, Error> {
         let mut total: Vec<Vec<u8>> = vec![];
         loop {
-            let (done, data) = match self.read_data_once() {
-                Some(d) => d,
-                None => return None
-            };
+            let (done, data) = self.read_data_once(max_len)?;
 
             // if we need more data then just breaking the loop
             // and returning what we have right now
@@ -274,7 +393,7 @@ impl TcpConnection {
             total.push(data);
         }
 
-        Some(total)
+        Ok(total)
     }
 
     /// Shutting down connection, this would be called before closing connection
@@ -282,7 +401,7 @@ impl TcpConnection {
     pub fn close(&self) {
         match self.socket.shutdown(Shutdown::Both) {
             Ok(_) => {},
-            Err(e) => Log::error("Error while trying to close connection", e.description())
+            Err(e) => Log::error("Error while trying to close connection", e.to_string().as_str())
         }
     }
 
@@ -290,15 +409,76 @@ impl TcpConnection {
     /// It will add data to "writable" as a write queue
     #[inline(always)]
     pub fn write(&mut self, data: Arc<Vec<u8>>, poll: &Poll) {
+        // other side wouldn't be able to read frames larger than agreed size
+        if let Some(max_frame) = self.capabilities.as_ref().and_then(|c| c.number(CAP_MAX_FRAME)) {
+            if data.len() as u64 > max_frame + 4 {
+                Log::warn("Skipping frame larger than connection max frame size"
+                          , format!("{} -> {} bytes", self.conn_token, data.len() - 4).as_str());
+                return;
+            }
+        }
+
+        // Nodes are sending binary events, so converting them if API client uses other codec
+        match self.codec.encode_raw(data) {
+            Ok(d) => self.write_raw(d, poll),
+            Err(e) => Log::warn("Unable to encode event for API client"
+                                , format!("{} -> {}", self.conn_token, e).as_str())
+        }
+    }
+
+    /// Writing control frame as is, without converting it with event codec
+    #[inline(always)]
+    pub fn write_raw(&mut self, data: Arc<Vec<u8>>, poll: &Poll) {
         self.writable.push_back(data);
         self.make_writable(poll);
     }
 
+    /// Accepting event codec for this connection
+    /// Codec is added to API version of our handshake, which is not written yet,
+    /// letting other side know which codec is going to be used
+    pub fn accept_codec(&mut self, codec: EventCodec) {
+        self.codec = codec;
+        let handshake = match self.handshake.take() {
+            Some(h) => h,
+            None => return
+        };
+
+        if codec == EventCodec::Binary {
+            self.writable.push_front(handshake);
+            return;
+        }
+
+        let mut handshake = (*handshake).clone();
+        let version = NetHelper::bytes_to_u32(&handshake, 0).unwrap_or(0);
+        NetHelper::u32_to_bytes(version | (u32::from(codec.id()) << API_CODEC_SHIFT), &mut handshake, 0);
+        self.writable.push_front(Arc::new(handshake));
+    }
+
     /// Tying to flush all data what we have right now in our socket
-    /// Returns None if there is a connection error
-    /// Returns Some(true) if queue is now empty
-    /// Returns Some(false) if we still have something in queue
-    pub fn flush(&mut self) -> Option<bool> {
+    /// Returns error if there is a connection error
+    /// Returns Ok(true) if queue is now empty
+    /// Returns Ok(false) if we still have something in queue
+    pub fn flush(&mut self) -> Result<bool, Error> {
+        if let Some(ref mut session) = self.tls {
+            // TLS session is keeping all encrypted data by itself
+            // so moving everything from queue to session and writing as much as we can
+            while let Some(data) = self.writable.pop_front() {
+                if let Err(e) = session.writer().write_all(&data) {
+                    return Err(Error::io("Unable to write to TLS session", e));
+                }
+            }
+
+            while session.wants_write() {
+                match session.write_tls(&mut self.socket) {
+                    Ok(_) => {}
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                    Err(e) => return Err(Error::io("Unable to write to connection", e))
+                }
+            }
+
+            return Ok(true);
+        }
+
         loop {
             {
                 let data = match self.writable.front() {
@@ -312,10 +492,10 @@ impl TcpConnection {
                         // if we got WouldBlock, then this is Non Blocking socket
                         // and data still not available for this, so it's not a connection error
                         if e.kind() == ErrorKind::WouldBlock {
-                            return Some(false)
+                            return Ok(false)
                         }
 
-                        return None;
+                        return Err(Error::io("Unable to write to connection", e));
                     }
                 };
 
@@ -323,7 +503,7 @@ impl TcpConnection {
                 // then moving forward index and waiting until next time
                 if write_len + self.writable_data_index < data.len() {
                     self.writable_data_index += write_len;
-                    return Some(false);
+                    return Ok(false);
                 }
 
                 // if we got here then our data is written
@@ -335,6 +515,11 @@ impl TcpConnection {
             self.writable.pop_front();
         }
 
-        Some(true)
+        // some streams are keeping converted data by themselves, like WebSocket
+        match self.socket.flush() {
+            Ok(_) => Ok(true),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(Error::io("Unable to flush connection", e))
+        }
     }
 }
\ No newline at end of file
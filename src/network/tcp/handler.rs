@@ -1,31 +1,44 @@
 #![allow(dead_code)]
 extern crate mio;
 
-use std::process;
-use std::error::Error;
 use std::sync::Arc;
-
-use network::tcp::TcpConnection;
-use network::{NetworkCommand, NetworkCMD, Slab, CONNECTION_COUNT_PRE_ALLOC, ConnectionIdentity, SocketType, Connection};
-use node::{NET_RECEIVER_CHANNEL_TOKEN, EVENT_LOOP_EVENTS_SIZE};
-use event::Event;
+use std::time::Duration;
+
+use network::tcp::{TcpConnection, TcpTls, TCP_FRAME_PING, TCP_FRAME_PONG, TCP_FRAME_CHALLENGE, TCP_FRAME_RESPONSE
+                   , TCP_FRAME_CAPABILITIES};
+use network::{NetworkCommand, NetworkCMD, Slab, CONNECTION_COUNT_PRE_ALLOC, ConnectionIdentity, Connection
+              , Capabilities, CAP_AUTH, CAP_HEARTBEAT, CAP_COMPRESSION, CAP_EVENT_VERSION, CAP_WILDCARDS
+              , CAPABILITIES_API_VERSION};
+use node::{NET_RECEIVER_CHANNEL_TOKEN, NET_TCP_TIMER_TOKEN, EVENT_LOOP_EVENTS_SIZE};
+use event::{Event, EventCodec, Compression, EVENT_VERSION_LEGACY};
 use helper::{Log, NetHelper};
+use config::NetworkingConfig;
+use auth::{Authenticator, AuthWorkers, NodeAuthenticator, NODE_CHALLENGE_LEN, NODE_RESPONSE_LEN};
+use api::API_ERROR;
+use error::Error;
 
 use self::mio::channel::{Sender, Receiver, channel};
 use self::mio::{Poll, Ready, PollOpt, Token, Events};
+use self::mio::timer::Timer;
 
 pub enum TcpHandlerCMD {
     None,
     HandleConnection,
-    WriteData
+    WriteData,
+    // result of API key check made by authentication worker
+    AuthResult,
+    // closing all connections and stopping handler event loop
+    Stop
 }
 
 pub struct TcpHandlerCommand {
     pub cmd: TcpHandlerCMD,
     pub conn: Vec<TcpConnection>,
     pub token: Vec<Token>,
-    pub data: Vec<Arc<Vec<u8>>>
-
+    pub data: Vec<Arc<Vec<u8>>>,
+    // API client token and result of its check, for matching it with the connection
+    pub auth_token: Vec<String>,
+    pub auth_result: Vec<Result<(), Error>>
 }
 
 impl TcpHandlerCommand {
@@ -34,11 +47,18 @@ impl TcpHandlerCommand {
             cmd: TcpHandlerCMD::None,
             conn: vec![],
             data: vec![],
-            token: vec![]
+            token: vec![],
+            auth_token: vec![],
+            auth_result: vec![]
         }
     }
 }
 
+/// Threads for calling blocking authenticator, like webhook
+const AUTH_WORKERS_COUNT: usize = 4;
+/// Connections waiting for authentication worker, new ones are rejected after this limit
+const AUTH_MAX_PENDING: usize = 1024;
+
 /// Main struct for handling TCP connections separately for reading and writing
 pub struct TcpHandler {
     // Connections for current handler
@@ -56,28 +76,99 @@ pub struct TcpHandler {
 
     // keeping index for this handler for later identification
     index: usize,
+
+    // timer for checking connections heartbeat
+    timer: Timer<()>,
+    // idle time before sending ping to connection, zero means heartbeat is disabled
+    heartbeat_interval: Duration,
+    // idle time before closing connection as a dead one
+    heartbeat_timeout: Duration,
+
+    // max frame sizes before and after connection is accepted
+    max_handshake_frame: usize,
+    max_event_frame: usize,
+
+    // checking API client tokens before accepting connection
+    authenticator: Option<Arc<dyn Authenticator>>,
+    // workers for blocking authenticator, so that it's not called from event loop
+    auth_workers: Option<AuthWorkers>,
+    // count of connections waiting for authentication workers
+    auth_pending: usize,
+    // challenge-response authentication for Node connections
+    node_authenticator: Option<Arc<NodeAuthenticator>>,
+    // connection token should match one of the names in peer TLS certificate
+    tls_bind_token: bool,
+    // our capabilities, None if we are using API version without capabilities exchange
+    capabilities: Option<Arc<Capabilities>>,
+    // event loop is running until Stop command
+    running: bool
 }
 
 impl TcpHandler {
     /// Making new TCP handler service
-    pub fn new(net_chan: Sender<NetworkCommand>, index: usize) -> TcpHandler {
+    pub fn new(net_chan: Sender<NetworkCommand>, index: usize, config: &NetworkingConfig
+               , authenticator: Option<Arc<dyn Authenticator>>
+               , node_authenticator: Option<Arc<NodeAuthenticator>>, tls_bind_token: bool
+               , capabilities: Option<Arc<Capabilities>>) -> Result<TcpHandler, Error> {
 
         let (s, r) = channel::<TcpHandlerCommand>();
+        let poll = match Poll::new() {
+            Ok(p) => p,
+            Err(e) => return Err(Error::io("Unable to make TcpHandler POLL service", e))
+        };
+
+        if let Err(e) = poll.register(&r, NET_RECEIVER_CHANNEL_TOKEN, Ready::readable(), PollOpt::level()) {
+            return Err(Error::io("Unable to register TcpHandler receiver channel", e));
+        }
 
-        TcpHandler {
+        // results from workers are coming back through our own channel
+        let auth_workers = match authenticator {
+            Some(ref a) if a.blocking() => {
+                let results = s.clone();
+                Some(AuthWorkers::new(a.clone(), AUTH_WORKERS_COUNT, move |socket_token, token, result| {
+                    let mut command = TcpHandlerCommand::new();
+                    command.cmd = TcpHandlerCMD::AuthResult;
+                    command.token.push(Token(socket_token));
+                    command.auth_token.push(token);
+                    command.auth_result.push(result);
+                    if let Err(e) = results.send(command) {
+                        Log::error("Unable to send authentication result to TcpHandler", e.to_string().as_str());
+                    }
+                }))
+            }
+            _ => None
+        };
+
+        let mut handler = TcpHandler {
             connections: Slab::with_capacity(CONNECTION_COUNT_PRE_ALLOC),
             sender_chan: s,
             receiver_chan: r,
-            net_chan: net_chan,
-            poll: match Poll::new() {
-                Ok(p) => p,
-                Err(e) => {
-                    Log::error("Unable to make TcpHandler POLL service", e.description());
-                    process::exit(1);
-                }
-            },
-            index: index
+            net_chan,
+            poll,
+            index,
+            timer: Timer::default(),
+            heartbeat_interval: Duration::from_millis(config.heartbeat_interval),
+            heartbeat_timeout: Duration::from_millis(config.heartbeat_timeout),
+            max_handshake_frame: config.max_handshake_frame,
+            max_event_frame: config.max_event_frame,
+            authenticator,
+            auth_workers,
+            auth_pending: 0,
+            node_authenticator,
+            tls_bind_token,
+            capabilities,
+            running: true
+        };
+
+        if handler.heartbeat_interval > Duration::from_millis(0) {
+            if let Err(e) = handler.poll.register(&handler.timer, NET_TCP_TIMER_TOKEN, Ready::readable(), PollOpt::edge()) {
+                return Err(Error::io("Unable to register TcpHandler timer", e));
+            }
+
+            handler.set_heartbeat_timeout();
         }
+
+        Ok(handler)
     }
 
     #[inline(always)]
@@ -87,18 +178,16 @@ impl TcpHandler {
 
     /// Main function to start TCP Handler service as a separate thread if needed
     pub fn start(&mut self) {
-        match self.poll.register(&self.receiver_chan, NET_RECEIVER_CHANNEL_TOKEN, Ready::readable(), PollOpt::level()) {
-            Ok(_) => {},
-            Err(e) => {
-                Log::error("Unable to register TcpHandler receiver channel", e.description());
-                process::exit(1);
-            }
-        }
-
         // making events for handling 5K events at once
         let mut events: Events = Events::with_capacity(EVENT_LOOP_EVENTS_SIZE);
-        loop {
-            let event_count = self.poll.poll(&mut events, None).unwrap();
+        while self.running {
+            let event_count = match self.poll.poll(&mut events, None) {
+                Ok(n) => n,
+                Err(e) => {
+                    Log::error("Unable to poll TcpHandler events", e.to_string().as_str());
+                    return;
+                }
+            };
             if event_count == 0 {
                 continue;
             }
@@ -117,6 +206,13 @@ impl TcpHandler {
                     continue;
                 }
 
+                if token == NET_TCP_TIMER_TOKEN {
+                    while self.timer.poll().is_some() {}
+                    self.heartbeat();
+                    self.set_heartbeat_timeout();
+                    continue;
+                }
+
                 // we tracking events only for our connections
                 if self.connections.contains(token) {
                     // if we got some error on one of the connections
@@ -129,6 +225,11 @@ impl TcpHandler {
                     // we only looking for readable connections
                     if kind.is_readable() {
                         self.readable(token);
+
+                        // TLS session could have handshake data which is not sent yet
+                        if self.connections.contains(token) && self.connections[token].wants_write() {
+                            self.connections[token].make_writable(&self.poll);
+                        }
                         continue;
                     }
 
@@ -173,11 +274,9 @@ impl TcpHandler {
                     }
 
                     // if connection is from client, then first of all we need to write handshake information
-                    if !conn.from_server {
-                        if !conn.make_writable(&self.poll) {
-                            Log::warn("Unable make writable TCP connection with TcpHandler POLL service", "Got connection by TcpHandleCommand");
-                            continue;
-                        }
+                    if !conn.from_server && !conn.make_writable(&self.poll) {
+                        Log::warn("Unable make writable TCP connection with TcpHandler POLL service", "Got connection by TcpHandleCommand");
+                        continue;
                     }
 
                     entry.insert(conn);
@@ -192,7 +291,7 @@ impl TcpHandler {
                         continue;
                     }
 
-                    let ref mut conn = self.connections[token];
+                    let conn = &mut self.connections[token];
 
                     // writing data to connection
                     // this will automatically make connection writable for poll service
@@ -201,43 +300,148 @@ impl TcpHandler {
                     }
                 }
             }
+            TcpHandlerCMD::AuthResult => {
+                while !command.token.is_empty() && !command.auth_token.is_empty() && !command.auth_result.is_empty() {
+                    let token = command.token.remove(0);
+                    let conn_token = command.auth_token.remove(0);
+                    let result = command.auth_result.remove(0);
+                    self.on_auth_result(token, &conn_token, result);
+                }
+            }
+            // connections are closed when handler is dropped after the event loop
+            TcpHandlerCMD::Stop => self.running = false,
             TcpHandlerCMD::None => {}
         }
     }
 
+    fn set_heartbeat_timeout(&mut self) {
+        if let Err(e) = self.timer.set_timeout(self.heartbeat_interval, ()) {
+            Log::error("Unable to schedule TcpHandler heartbeat", e.to_string().as_str());
+        }
+    }
+
+    /// Closing connections which are not sending anything during heartbeat timeout
+    /// and sending ping to accepted connections which are idle during heartbeat interval
+    /// Only peers which advertised heartbeat support are getting pings, others are closed by read or hup errors
+    /// Connections which didn't finish handshake are closed after heartbeat timeout in any case
+    fn heartbeat(&mut self) {
+        let mut dead = vec![];
+        let mut idle = vec![];
+        for conn in self.connections.iter() {
+            let idle_time = conn.idle();
+            // other side without heartbeat support wouldn't answer to our pings
+            let heartbeat = match conn.capabilities {
+                Some(ref c) => c.get(CAP_HEARTBEAT) == Some("1"),
+                None => conn.socket.native_heartbeat()
+            };
+
+            if !heartbeat {
+                if !conn.accepted && idle_time >= self.heartbeat_timeout {
+                    dead.push(conn.socket_token);
+                }
+                continue;
+            }
+
+            if idle_time >= self.heartbeat_timeout {
+                dead.push(conn.socket_token);
+            } else if idle_time >= self.heartbeat_interval && conn.accepted {
+                idle.push(conn.socket_token);
+            }
+        }
+
+        for token in dead {
+            Log::warn("Closing dead TCP connection", self.connections[token].conn_token.as_str());
+            self.close_connection(token);
+        }
+
+        if idle.is_empty() {
+            return;
+        }
+
+        let ping = Arc::new(vec![0, 0, 0, 1, TCP_FRAME_PING]);
+        for token in idle {
+            self.connections[token].write(ping.clone(), &self.poll);
+        }
+    }
+
     #[inline(always)]
     fn readable(&mut self, token: Token) {
-        let accepted = {
-            let ref conn: TcpConnection = self.connections[token];
-            Connection::check_api_version(conn.api_version) && conn.conn_token.len() > 0
+        let (accepted, handshake_done) = {
+            let conn = &mut self.connections[token];
+            // rejected connection is only waiting to write error message
+            // and connection waiting for authentication worker is read after getting result
+            if conn.closing || conn.authenticating {
+                return;
+            }
+
+            // any data from connection means that it's still alive
+            conn.touch();
+            (conn.accepted, Connection::check_api_version(conn.api_version) && !conn.conn_token.is_empty())
         };
 
         if !accepted {
-            // if we don't have handshake information
-            // trying to read again
-            if !self.read_handshake_info(token) {
+            if !handshake_done {
+                // if we don't have handshake information
+                // trying to read again
+                if !self.read_handshake_info(token) {
+                    return
+                }
+
+                if let Err(reason) = self.authenticate(token) {
+                    self.reject_connection(token, reason.to_string().as_str());
+                    return
+                }
+
+                // blocking authenticator is called from worker thread,
+                // and handshake is continued after getting result from it
+                if self.auth_workers.is_some() && self.needs_api_auth(token) {
+                    self.authenticate_later(token);
+                    return
+                }
+
+                self.authenticated(token);
+            }
+
+            if self.handshake_pending(token) && !self.read_handshake_frames(token) {
                 return
             }
 
-            // if we got handshake information and connection is from server
-            // making writable to send our handshake information
-            let ref conn: TcpConnection = self.connections[token];
-            if conn.from_server {
-                conn.make_writable(&self.poll);
+            if !self.verify_node(token) {
+                return
             }
 
             self.accept_connection(token);
-            return
+
+            // other side could send data right after handshake
+            // so reading everything what we have in socket
         }
 
-        let (close_conn, data_list, conn_token) = {
-            let ref mut conn = self.connections[token];
-            match conn.read_data() {
-                Some(d) => (false, d, conn.conn_token.clone()),
-                None => {
-                    // if we got None then there is something wrong with this connection
+        let (close_conn, data_list, conn_token, codec) = {
+            let conn = &mut self.connections[token];
+            match conn.read_data(self.max_event_frame) {
+                Ok(mut d) => {
+                    // heartbeat frames are handled here, without sending them to Networking
+                    let mut ping = false;
+                    d.retain(|data| {
+                        if data.len() != 1 {
+                            return true;
+                        }
+
+                        ping = ping || data[0] == TCP_FRAME_PING;
+                        false
+                    });
+
+                    if ping {
+                        conn.write(Arc::new(vec![0, 0, 0, 1, TCP_FRAME_PONG]), &self.poll);
+                    }
+
+                    (false, d, conn.conn_token.clone(), conn.codec)
+                },
+                Err(e) => {
+                    // if we got error then there is something wrong with this connection
                     // we need to close it
-                    (true, vec![], String::new())
+                    TcpHandler::log_connection_error(conn, &e);
+                    (true, vec![], String::new(), EventCodec::Binary)
                 }
             }
         };
@@ -247,7 +451,7 @@ impl TcpHandler {
             return;
         }
 
-        if data_list.len() == 0 {
+        if data_list.is_empty() {
             return;
         }
 
@@ -256,39 +460,53 @@ impl TcpHandler {
         event_cmd.token = vec![conn_token];
         event_cmd.event.reserve_exact(data_list.len());
         for data in data_list {
-            event_cmd.event.push(match Event::from_raw(&data) {
-                Some(e) => e,
-                None => continue
-            });
+            let mut event = match codec.decode(&data) {
+                Ok(e) => e,
+                Err(e) => {
+                    Log::warn("Dropping invalid event", format!("{} -> {}", event_cmd.token[0], e).as_str());
+                    continue;
+                }
+            };
+
+            // compressed event data could be bigger than frame, so it's limited with the same max size
+            if let Err(e) = event.decompress(self.max_event_frame) {
+                Log::warn("Dropping event with invalid compressed data", format!("{} -> {}", event_cmd.token[0], e).as_str());
+                continue;
+            }
+
+            event_cmd.event.push(event);
         }
 
         match self.net_chan.send(event_cmd) {
             Ok(_) => {},
-            Err(e) => Log::error("Unable to send data over networking channel from TCP Reader", e.description())
+            Err(e) => Log::error("Unable to send data over networking channel from TCP Reader", e.to_string().as_str())
         }
     }
 
     #[inline(always)]
     fn writable(&mut self, token: Token) {
         let close_conn = {
-            let ref mut conn = self.connections[token];
+            let conn = &mut self.connections[token];
             match conn.flush() {
-                Some(done) => {
+                Ok(done) => {
                     if done {
                         // if we are done with flushing write queue
                         // making connection readable again
                         conn.make_readable(&self.poll);
                     }
 
-                    false
+                    // rejected connection is closed after getting error message
+                    done && conn.closing
                 },
-                None => true
+                Err(e) => {
+                    TcpHandler::log_connection_error(conn, &e);
+                    true
+                }
             }
         };
 
         if close_conn {
             self.close_connection(token);
-            return
         }
     }
 
@@ -297,16 +515,18 @@ impl TcpHandler {
         // sending command to Networking that connection closed
         // or at least one channel was closed for this connection
         {
-            let ref conn = self.connections[token];
+            let conn = &self.connections[token];
             // if we have accepted connection, notifying about close action
-            if Connection::check_api_version(conn.api_version) && conn.conn_token.len() > 0 {
+            if conn.accepted {
                 let mut net_cmd = NetworkCommand::new();
                 net_cmd.cmd = NetworkCMD::ConnectionClose;
                 net_cmd.token = vec![conn.conn_token.clone()];
                 net_cmd.conn_identity.push(ConnectionIdentity {
-                    socket_type: SocketType::TCP,
+                    socket_type: conn.socket.socket_type(),
                     handler_index: self.index,
-                    socket_token: token
+                    socket_token: token,
+                    compression: Compression::None,
+                    event_version: EVENT_VERSION_LEGACY
                 });
                 match self.net_chan.send(net_cmd) {
                     Ok(_) => {}
@@ -315,6 +535,19 @@ impl TcpHandler {
                                    , format!("Connection Close Command for Token - {} -> {}", conn.conn_token.clone(), e).as_str());
                     }
                 }
+            } else if !conn.address.is_empty() {
+                // connection made by us closed before handshake
+                // so letting Networking know that connection attempt failed
+                let mut net_cmd = NetworkCommand::new();
+                net_cmd.cmd = NetworkCMD::ConnectionFailed;
+                net_cmd.address = vec![conn.address.clone()];
+                match self.net_chan.send(net_cmd) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        Log::error("Unable to send command to networking from TcpHandler"
+                                   , format!("Connection Failed Command for Address - {} -> {}", conn.address.clone(), e).as_str());
+                    }
+                }
             }
         }
         self.connections.remove(token);
@@ -324,17 +557,18 @@ impl TcpHandler {
     fn read_handshake_info(&mut self, token: Token) -> bool {
         // if we got here then we have connection with this token
         let mut close_conn = {
-            let ref mut conn: TcpConnection = self.connections[token];
+            let conn = &mut self.connections[token];
             // if we don't have yet API version defined
             if !Connection::check_api_version(conn.api_version) {
                 match conn.read_api_version() {
-                    Some((done, version)) => {
+                    Ok((done, version)) => {
                         // if we not done with reading API version
                         // Just returning and waiting until next readable cycle
                         if !done {
                             return false;
                         }
 
+                        let (version, codec) = Connection::split_api_version(version);
                         // if we got wrong API version just closing connection
                         if !Connection::check_api_version(version) {
                             true
@@ -342,12 +576,16 @@ impl TcpHandler {
                             // if we got valid API version
                             // saving it as a connection version
                             conn.api_version = version;
+                            conn.requested_codec = codec;
                             false
                         }
                     }
 
                     // if we have connection error closing it
-                    None => true
+                    Err(e) => {
+                        TcpHandler::log_connection_error(conn, &e);
+                        true
+                    }
                 }
             } else {
                 false
@@ -360,12 +598,12 @@ impl TcpHandler {
         }
 
         close_conn = {
-            let ref mut conn: TcpConnection = self.connections[token];
+            let conn = &mut self.connections[token];
             // if we don't have token and value form connection
-            if conn.conn_token.len() == 0 {
+            if conn.conn_token.is_empty() {
                 // reading Connection Token and Value
-                match conn.read_token_value() {
-                    Some((done, token_str, value)) => {
+                match conn.read_token_value(self.max_handshake_frame) {
+                    Ok((done, token_str, value)) => {
                         // if we not done with reading API version
                         // Just returning and waiting until next readable cycle
                         if !done {
@@ -383,12 +621,19 @@ impl TcpHandler {
                             conn.conn_token = token_str;
                             conn.conn_value = value;
 
+                            // only API clients could use other codec, unknown codec is falling back to binary
+                            let codec = if value == 0 { EventCodec::from_id(conn.requested_codec) } else { None };
+                            conn.accept_codec(codec.unwrap_or(EventCodec::Binary));
+
                             false
                         }
                     }
 
                     // if we have connection error closing it
-                    None => true
+                    Err(e) => {
+                        TcpHandler::log_connection_error(conn, &e);
+                        true
+                    }
                 }
             } else {
                 false
@@ -403,18 +648,325 @@ impl TcpHandler {
         true
     }
 
+    /// Sending our handshake information, capabilities and challenge after connection is authenticated
+    fn authenticated(&mut self, token: Token) {
+        // if we got handshake information and connection is from server
+        // making writable to send our handshake information
+        let is_node = {
+            let conn = &self.connections[token];
+            if conn.from_server {
+                conn.make_writable(&self.poll);
+            }
+
+            conn.conn_value != 0
+        };
+
+        self.send_capabilities(token);
+
+        // Nodes should prove that they are part of our cluster
+        // challenge is sent only with capabilities exchange, where other side knows that it should wait for it
+        if is_node && self.node_authenticator.is_some() && self.connections[token].capabilities_expected {
+            self.send_challenge(token);
+        }
+    }
+
+    /// API client tokens are checked only for connections accepted by our server
+    #[inline(always)]
+    fn needs_api_auth(&self, token: Token) -> bool {
+        let conn = &self.connections[token];
+        self.authenticator.is_some() && conn.from_server && conn.conn_value == 0
+    }
+
+    /// Giving API client token to authentication workers, connection is not read until we get result
+    fn authenticate_later(&mut self, token: Token) {
+        if self.auth_pending >= AUTH_MAX_PENDING {
+            self.reject_connection(token, "Too many connections are waiting for authentication");
+            return;
+        }
+
+        let sent = match self.auth_workers {
+            Some(ref w) => w.check(token.0, &self.connections[token].conn_token),
+            None => false
+        };
+
+        if !sent {
+            self.reject_connection(token, "Authentication service is not available");
+            return;
+        }
+
+        self.auth_pending += 1;
+        self.connections[token].authenticating = true;
+    }
+
+    /// Continuing handshake of connection which was waiting for authentication worker
+    fn on_auth_result(&mut self, token: Token, conn_token: &str, result: Result<(), Error>) {
+        self.auth_pending = self.auth_pending.saturating_sub(1);
+        // connection could be closed, and its slot could be given to other connection meanwhile
+        let waiting = self.connections.contains(token)
+                        && self.connections[token].authenticating
+                        && self.connections[token].conn_token == conn_token;
+        if !waiting {
+            return;
+        }
+
+        self.connections[token].authenticating = false;
+        if let Err(reason) = result {
+            self.reject_connection(token, reason.to_string().as_str());
+            return;
+        }
+
+        self.authenticated(token);
+        // data received during authentication is not giving new readable event
+        self.readable(token);
+    }
+
+    /// Checking API client token, Node connections are not checked here
+    /// Blocking authenticators are skipped, they are called later from authentication workers
+    /// If TLS token binding is enabled, every connection token is checked against peer certificate
+    fn authenticate(&self, token: Token) -> Result<(), Error> {
+        // local connections are not using TLS, they are protected by socket file permissions
+        if self.tls_bind_token && !self.connections[token].socket.is_local() {
+            let conn = &self.connections[token];
+            let valid = match conn.tls {
+                Some(ref session) => TcpTls::peer_has_name(session, &conn.conn_token),
+                None => false
+            };
+
+            if !valid {
+                return Err(Error::Auth(String::from("Connection token doesn't match TLS certificate")));
+            }
+        }
+
+        match self.authenticator {
+            Some(ref a) if !a.blocking() && self.needs_api_auth(token) => a.authenticate(&self.connections[token].conn_token),
+            _ => Ok(())
+        }
+    }
+
+    /// Logging reason of closing connection, closed connections are not logged as errors
+    fn log_connection_error(conn: &TcpConnection, e: &Error) {
+        if e.is_closed() {
+            return;
+        }
+
+        Log::warn("Closing TCP connection", format!("{} -> {}", conn.conn_token, e).as_str());
+    }
+
+    /// Sending our handshake with error event to connection and closing it after that
+    fn reject_connection(&mut self, token: Token, reason: &str) {
+        let conn = &mut self.connections[token];
+        Log::warn("Rejecting API connection", format!("{} -> {}", conn.conn_token, reason).as_str());
+
+        let ev = Event {
+            name: String::from(API_ERROR),
+            data: Vec::from(reason),
+            ..Event::default()
+        };
+        conn.closing = true;
+        match ev.to_raw() {
+            // handshake information is already in write queue
+            Some(data) => conn.write(Arc::new(data), &self.poll),
+            None => { conn.make_writable(&self.poll); }
+        }
+    }
+
+    /// Sending random challenge to other Node
+    fn send_challenge(&mut self, token: Token) {
+        let challenge = match self.node_authenticator {
+            Some(ref a) => a.challenge(),
+            None => return
+        };
+
+        self.write_control_frame(token, TCP_FRAME_CHALLENGE, &challenge);
+        self.connections[token].auth_challenge = challenge;
+    }
+
+    /// Sending our capabilities, if both sides are using API version with capabilities exchange
+    fn send_capabilities(&mut self, token: Token) {
+        let data = match self.capabilities {
+            Some(ref caps) if self.connections[token].api_version >= CAPABILITIES_API_VERSION => caps.to_bytes(),
+            _ => return
+        };
+
+        self.write_control_frame(token, TCP_FRAME_CAPABILITIES, &data);
+        self.connections[token].capabilities_expected = true;
+    }
+
+    /// Reading capabilities, challenge and response frames which are sent before connection is accepted
+    /// Returns true when everything is received and connection could be accepted
+    fn read_handshake_frames(&mut self, token: Token) -> bool {
+        loop {
+            let (done, data) = match self.connections[token].read_data_once(self.max_handshake_frame) {
+                Ok(d) => d,
+                Err(e) => {
+                    TcpHandler::log_connection_error(&self.connections[token], &e);
+                    self.close_connection(token);
+                    return false;
+                }
+            };
+
+            if !done {
+                return false;
+            }
+
+            let kind = data.first().cloned().unwrap_or(0);
+            if kind == TCP_FRAME_CHALLENGE && data.len() == 1 + NODE_CHALLENGE_LEN {
+                self.connections[token].auth_peer_challenge = data[1..].to_vec();
+                self.respond_challenge(token);
+            } else if kind == TCP_FRAME_RESPONSE && data.len() == 1 + NODE_RESPONSE_LEN {
+                let verified = match self.node_authenticator {
+                    Some(ref a) => {
+                        let conn = &self.connections[token];
+                        a.verify(&conn.auth_challenge, &conn.auth_peer_challenge, &conn.conn_token, &data[1..])
+                    }
+                    None => true
+                };
+
+                self.connections[token].auth_verified = Some(verified);
+                self.respond_challenge(token);
+            } else if kind == TCP_FRAME_CAPABILITIES && self.connections[token].capabilities_expected {
+                let theirs = match Capabilities::from_bytes(&data[1..]) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        TcpHandler::log_connection_error(&self.connections[token], &e);
+                        self.close_connection(token);
+                        return false;
+                    }
+                };
+
+                let auth_expected = theirs.supports(CAP_AUTH, "challenge");
+                let agreed = match self.capabilities {
+                    Some(ref ours) => ours.intersect(&theirs),
+                    None => theirs
+                };
+
+                let conn = &mut self.connections[token];
+                conn.auth_expected = conn.conn_value != 0 && auth_expected;
+                conn.capabilities = Some(agreed);
+                conn.capabilities_expected = false;
+            } else if data.len() != 1 {
+                // heartbeat frames are fine here, everything else is a protocol error
+                Log::warn("Unexpected frame during connection handshake", self.connections[token].conn_token.as_str());
+                self.close_connection(token);
+                return false;
+            }
+
+            if !self.handshake_pending(token) {
+                return true;
+            }
+        }
+    }
+
+    /// Responding to the challenge from other Node, our own challenge is already sent at this point
+    /// Node which accepted connection is responding only after other side proved itself,
+    /// otherwise anyone could connect to us with other Node token and relay our response to that Node
+    fn respond_challenge(&mut self, token: Token) {
+        let response = {
+            let conn = &self.connections[token];
+            if conn.auth_responded || conn.auth_peer_challenge.is_empty() {
+                return;
+            }
+
+            match self.node_authenticator {
+                Some(ref a) if conn.from_server && !conn.auth_challenge.is_empty() => match conn.auth_verified {
+                    Some(true) => a.respond(&conn.auth_peer_challenge, &conn.auth_challenge, &conn.conn_token),
+                    // Node which failed verification is getting invalid response
+                    Some(false) => vec![0; NODE_RESPONSE_LEN],
+                    None => return
+                },
+                Some(ref a) => a.respond(&conn.auth_peer_challenge, &conn.auth_challenge, &conn.conn_token),
+                None => vec![0; NODE_RESPONSE_LEN]
+            }
+        };
+
+        self.write_control_frame(token, TCP_FRAME_RESPONSE, &response);
+        self.connections[token].auth_responded = true;
+    }
+
+    /// Checking if we are still waiting for capabilities, challenge or response frames from other side
+    fn handshake_pending(&self, token: Token) -> bool {
+        let conn = &self.connections[token];
+        if conn.capabilities_expected {
+            return true;
+        }
+
+        conn.conn_value != 0
+            && ((conn.auth_expected && !conn.auth_responded)
+                || (!conn.auth_challenge.is_empty() && conn.auth_verified.is_none()))
+    }
+
+    /// Checking Node response for our challenge, if Node authentication is enabled
+    /// Node which couldn't prove that it's part of our cluster could only send and receive events as an API client,
+    /// but if API clients are authenticated, it's rejected, because it didn't give any API key
+    /// Returns false if connection is rejected
+    fn verify_node(&mut self, token: Token) -> bool {
+        {
+            let conn = &self.connections[token];
+            // Node using API version without capabilities couldn't be challenged, so it's not verified
+            if conn.conn_value == 0 || self.node_authenticator.is_none() || conn.auth_verified == Some(true) {
+                return true;
+            }
+        }
+
+        if self.authenticator.is_some() {
+            self.reject_connection(token, "Node authentication failed");
+            return false;
+        }
+
+        let conn = &mut self.connections[token];
+        Log::warn("Node authentication failed, accepting it as an API client", conn.conn_token.as_str());
+        conn.conn_value = 0;
+        true
+    }
+
+    fn write_control_frame(&mut self, token: Token, kind: u8, data: &[u8]) {
+        let mut frame = vec![0; 5 + data.len()];
+        NetHelper::u32_to_bytes((1 + data.len()) as u32, &mut frame, 0);
+        frame[4] = kind;
+        frame[5..].copy_from_slice(data);
+        self.connections[token].write_raw(Arc::new(frame), &self.poll);
+    }
+
     #[inline(always)]
-    fn accept_connection(&self, token: Token) {
-        let ref conn = self.connections[token];
+    fn accept_connection(&mut self, token: Token) {
+        self.connections[token].accepted = true;
+        let conn = &self.connections[token];
         // notifying Networking about new connection accepted
         let mut net_cmd = NetworkCommand::new();
         net_cmd.cmd = NetworkCMD::HandleConnection;
         net_cmd.token.push(conn.conn_token.clone());
         net_cmd.value.push(conn.conn_value);
+        net_cmd.address.push(conn.address.clone());
+        // using first compression from agreed list, which is in our order of preference
+        // API clients are getting events without compression
+        let compression = match conn.capabilities {
+            Some(ref caps) if conn.conn_value != 0 => caps.list(CAP_COMPRESSION).iter()
+                                                        .filter_map(|c| Compression::from_name(c))
+                                                        .next().unwrap_or(Compression::None),
+            _ => Compression::None
+        };
+        // using latest event encoding which is supported by both sides
+        let event_version = match conn.capabilities {
+            Some(ref caps) => caps.list(CAP_EVENT_VERSION).iter()
+                                .filter_map(|v| v.parse::<u8>().ok())
+                                .max().unwrap_or(EVENT_VERSION_LEGACY),
+            None => EVENT_VERSION_LEGACY
+        };
+        // events published through older Node are not matching wildcard subscriptions
+        let wildcards = match conn.capabilities {
+            Some(ref caps) => caps.supports(CAP_WILDCARDS, "1"),
+            None => false
+        };
+        if conn.conn_value != 0 && !wildcards {
+            Log::warn("Node doesn't support wildcard subscriptions, events published through it are matched only by exact names"
+                      , conn.conn_token.as_str());
+        }
         net_cmd.conn_identity.push(ConnectionIdentity {
             handler_index: self.index,
-            socket_type: SocketType::TCP,
-            socket_token: conn.socket_token
+            socket_type: conn.socket.socket_type(),
+            socket_token: conn.socket_token,
+            compression,
+            event_version
         });
         match self.net_chan.send(net_cmd) {
             Ok(_) => {}
@@ -8,14 +8,14 @@ use self::mio::{Ready, PollOpt, Token};
 use self::mio::channel::Sender;
 
 use node::{Node, NET_TCP_SERVER_TOKEN};
-use network::{TcpConnection
+use error::Error;
+use network::{TcpConnection, Stream
               , TcpHandler, Networking
-              , TcpHandlerCommand, TcpHandlerCMD};
+              , TcpHandlerCommand, TcpHandlerCMD
+              , Capabilities, CAPABILITIES_API_VERSION};
 
 
 use std::net::SocketAddr;
-use std::error::Error;
-use std::process;
 use std::str::FromStr;
 use std::io::ErrorKind;
 use std::thread;
@@ -25,10 +25,10 @@ use std::sync::Arc;
 /// On top of Node structure
 pub trait TcpNetwork {
     /// Min function to attach TCP service functionality to existing POLL service
-    fn register_tcp(&mut self);
+    fn register_tcp(&mut self) -> Result<(), Error>;
 
     /// Make TCP server socket listener from given address
-    fn make_tcp_server(address: &str) -> TcpListener;
+    fn make_tcp_server(address: &str) -> Result<TcpListener, Error>;
 
     /// Handler for event loop ready event
     /// This is general event processing for TCP connections/servers
@@ -47,50 +47,54 @@ pub trait TcpNetwork {
     fn tcp_connect(&mut self, address: &str) -> bool;
 
     /// Transferring connection from pending to one of the TCP handlers
-    fn tcp_transfer_connection(&mut self, sock: TcpStream, from_server: bool);
+    /// Address is given only for connections made by us, to know where to reconnect
+    /// Returns false if connection is dropped before reaching TCP handler
+    fn tcp_transfer_connection(&mut self, sock: TcpStream, from_server: bool, address: &str) -> bool;
 }
 
 impl TcpNetwork for Node {
-    fn register_tcp(&mut self) {
-        match self.poll.register(&self.net_tcp_server, NET_TCP_SERVER_TOKEN, Ready::readable(), PollOpt::edge()) {
-            Ok(_) => {}
-            Err(e) => {
-                Log::error("Unable to register TCP server to Node POLL service", e.description());
-                process::exit(1);
-            }
+    fn register_tcp(&mut self) -> Result<(), Error> {
+        if let Err(e) = self.poll.register(&self.net_tcp_server, NET_TCP_SERVER_TOKEN, Ready::readable(), PollOpt::edge()) {
+            return Err(Error::io("Unable to register TCP server to Node POLL service", e));
         }
 
         // making TCP handlers based on initial allocated capacity
         let handlers_count = self.net_tcp_handler_sender_chan.capacity();
         if handlers_count == 0 {
-            Log::warn("There is no concurrency defined, exiting process!", "From TcpNetworking Registering functionality");
-            process::exit(1);
+            return Err(Error::Config(String::from("There is no concurrency defined for TCP handlers")));
         }
 
+        // capabilities are exchanged only if we are using API version which supports them
+        let capabilities = if self.api_version >= CAPABILITIES_API_VERSION {
+            Some(Arc::new(Capabilities::local(&self.net_config, self.net_node_authenticator.is_some())))
+        } else {
+            None
+        };
+
         for i in 0..handlers_count {
-            let mut handler = TcpHandler::new(self.net_sender_chan.clone(), i);
+            let mut handler = TcpHandler::new(self.net_sender_chan.clone(), i, &self.net_config
+                                              , self.net_authenticator.clone()
+                                              , self.net_node_authenticator.clone()
+                                              , self.net_tls.as_ref().map(|t| t.bind_token()).unwrap_or(false)
+                                              , capabilities.clone())?;
             self.net_tcp_handler_sender_chan.push(handler.channel());
             thread::spawn(move || {
                 handler.start();
             });
         }
+
+        Ok(())
     }
 
-    fn make_tcp_server(address: &str) -> TcpListener {
+    fn make_tcp_server(address: &str) -> Result<TcpListener, Error> {
         let addr = match SocketAddr::from_str(address) {
             Ok(a) => a,
-            Err(e) => {
-                Log::error("Unable to parse given TCP server address", e.description());
-                process::exit(1);
-            }
+            Err(e) => return Err(Error::Config(format!("Unable to parse given TCP server address {} -> {}", address, e)))
         };
 
         match TcpListener::bind(&addr) {
-            Ok(s) => s,
-            Err(e) => {
-                Log::error("Unable to bind given TCP server address", e.description());
-                process::exit(1);
-            }
+            Ok(s) => Ok(s),
+            Err(e) => Err(Error::Io(format!("Unable to bind given TCP server address {}", address), e))
         }
     }
 
@@ -118,13 +122,13 @@ impl TcpNetwork for Node {
                     // if we got WouldBlock, then this is Non Blocking socket
                     // and data still not available for this, so it's not a connection error
                     if e.kind() != ErrorKind::WouldBlock {
-                        Log::error("Unable to accept connection from TCP server socket", e.description());
+                        Log::error("Unable to accept connection from TCP server socket", e.to_string().as_str());
                     }
                     return;
                 }
             };
 
-            self.tcp_transfer_connection(sock, true);
+            self.tcp_transfer_connection(sock, true, "");
         };
     }
 
@@ -145,7 +149,7 @@ impl TcpNetwork for Node {
         let sock_address = match SocketAddr::from_str(address) {
             Ok(a) => a,
             Err(e) => {
-                Log::error(format!("Unable to parse address for making connection to TCP server {}", address).as_str(), e.description());
+                Log::error(format!("Unable to parse address for making connection to TCP server {}", address).as_str(), e.to_string().as_str());
                 return false;
             }
         };
@@ -153,27 +157,34 @@ impl TcpNetwork for Node {
         let sock = match TcpStream::connect(&sock_address) {
             Ok(s) => s,
             Err(e) => {
-                Log::error(format!("Unable to connect with given tcp address {}", address).as_str(), e.description());
+                Log::error(format!("Unable to connect with given tcp address {}", address).as_str(), e.to_string().as_str());
                 return false;
             }
         };
 
-        self.tcp_transfer_connection(sock, false);
-        true
+        self.tcp_transfer_connection(sock, false, address)
     }
 
     #[inline(always)]
-    fn tcp_transfer_connection(&mut self, sock: TcpStream, from_server: bool) {
+    fn tcp_transfer_connection(&mut self, sock: TcpStream, from_server: bool, address: &str) -> bool {
         let mut command = TcpHandlerCommand::new();
         command.cmd = TcpHandlerCMD::HandleConnection;
-        command.conn.push(TcpConnection::new(sock, Token(0), from_server));
+        command.conn.push(TcpConnection::new(Stream::Tcp(sock), Token(0), from_server));
+        command.conn[0].address = String::from(address);
+        if let Some(ref tls) = self.net_tls {
+            command.conn[0].tls = match if from_server { tls.server_connection() } else { tls.client_connection(address) } {
+                Some(s) => Some(s),
+                // connection without TLS wouldn't be accepted by other side anyway
+                None => return false
+            };
+        }
         // adding handshake info, for writing it later from handler
-        command.conn[0].add_writable_data(Arc::new(self.handshake_info()));
+        command.conn[0].add_handshake(Arc::new(self.handshake_info()));
         match self.tcp_get_handler().send(command) {
-            Ok(_) => {},
+            Ok(_) => true,
             Err(e) => {
-                Log::error("Unable to send HandleConnection command to TCP handler", e.description());
-                return;
+                Log::error("Unable to send HandleConnection command to TCP handler", e.to_string().as_str());
+                false
             }
         }
     }
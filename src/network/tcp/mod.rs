@@ -4,11 +4,25 @@ extern crate slab;
 mod main;
 mod handler;
 mod conn;
+mod tls;
 
 pub use self::main::TcpNetwork;
 pub use self::handler::{TcpHandlerCMD, TcpHandlerCommand, TcpHandler};
 pub use self::conn::{TcpConnection};
+pub use self::tls::TcpTls;
 
 use self::mio::Token;
 
-pub type Slab<T> = slab::Slab<T, Token>;
\ No newline at end of file
+pub type Slab<T> = slab::Slab<T, Token>;
+
+/// Heartbeat frames are only 1 byte long, so they couldn't be mixed with events
+pub const TCP_FRAME_PING: u8 = 1;
+pub const TCP_FRAME_PONG: u8 = 2;
+
+/// Frames for challenge-response authentication between Nodes
+/// they are sent only after handshake, before connection is accepted
+pub const TCP_FRAME_CHALLENGE: u8 = 3;
+pub const TCP_FRAME_RESPONSE: u8 = 4;
+
+/// Frame with supported capabilities, sent after handshake if both sides are using API version with capabilities
+pub const TCP_FRAME_CAPABILITIES: u8 = 5;
\ No newline at end of file
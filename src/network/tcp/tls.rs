@@ -0,0 +1,194 @@
+#![allow(dead_code)]
+extern crate rustls;
+extern crate rustls_pemfile;
+extern crate webpki;
+
+use self::rustls::{ServerConfig, ClientConfig, ServerConnection, ClientConnection, Connection, RootCertStore};
+use self::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use self::rustls::server::WebPkiClientVerifier;
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use config::NetworkingConfig;
+use helper::Log;
+use error::Error;
+
+/// TLS configuration for TCP connections
+/// Server side is used for accepted connections, and client side for connections made by us
+pub struct TcpTls {
+    server: Arc<ServerConfig>,
+    // client side is available only if we have CA for verifying other Nodes
+    client: Option<Arc<ClientConfig>>,
+    // name for verifying certificate of the Node we are connecting to
+    // if empty, IP address from connection address is used
+    server_name: String,
+    // connection token should be one of the names in peer certificate
+    bind_token: bool,
+}
+
+impl TcpTls {
+    /// Loading certificates and keys from PEM files given in configuration
+    /// Returns None if TLS is not configured
+    pub fn load(config: &NetworkingConfig) -> Result<Option<TcpTls>, Error> {
+        if config.tls_cert.is_empty() && config.tls_key.is_empty() {
+            return Ok(None);
+        }
+
+        let certs = TcpTls::load_certs(&config.tls_cert)?;
+        let key = TcpTls::load_key(&config.tls_key)?;
+
+        let roots = if config.tls_ca.is_empty() {
+            None
+        } else {
+            let mut roots = RootCertStore::empty();
+            for cert in TcpTls::load_certs(&config.tls_ca)? {
+                if let Err(e) = roots.add(cert) {
+                    return Err(Error::Config(format!("Unable to add CA certificate from {} -> {}", config.tls_ca, e)));
+                }
+            }
+            Some(Arc::new(roots))
+        };
+
+        let server_builder = ServerConfig::builder();
+        let server_builder = match (config.tls_client_auth, roots.clone()) {
+            (true, Some(r)) => match WebPkiClientVerifier::builder(r).build() {
+                Ok(verifier) => server_builder.with_client_cert_verifier(verifier),
+                Err(e) => return Err(Error::Config(format!("Unable to make TLS client certificate verifier -> {}", e)))
+            },
+            (true, None) => return Err(Error::Config(String::from("TLS CA file is required for verifying client certificates"))),
+            (false, _) => server_builder.with_no_client_auth()
+        };
+
+        let server = match server_builder.with_single_cert(certs.clone(), key.clone_key()) {
+            Ok(s) => s,
+            Err(e) => return Err(Error::Config(format!("Unable to make TLS server configuration -> {}", e)))
+        };
+
+        // our certificate is also given as a client certificate, so that other Node could verify us
+        let client = match roots {
+            Some(r) => match ClientConfig::builder().with_root_certificates(r).with_client_auth_cert(certs, key) {
+                Ok(c) => Some(Arc::new(c)),
+                Err(e) => return Err(Error::Config(format!("Unable to make TLS client configuration -> {}", e)))
+            },
+            None => None
+        };
+
+        Ok(Some(TcpTls {
+            server: Arc::new(server),
+            client,
+            server_name: config.tls_server_name.clone(),
+            bind_token: config.tls_bind_token
+        }))
+    }
+
+    /// Making TLS session for accepted connection
+    pub fn server_connection(&self) -> Option<Connection> {
+        match ServerConnection::new(self.server.clone()) {
+            Ok(c) => Some(TcpTls::unlimited(Connection::Server(c))),
+            Err(e) => {
+                Log::error("Unable to make TLS server connection", e.to_string().as_str());
+                None
+            }
+        }
+    }
+
+    /// Making TLS session for connection to given address
+    pub fn client_connection(&self, address: &str) -> Option<Connection> {
+        let config = match self.client {
+            Some(ref c) => c.clone(),
+            None => {
+                Log::error("Unable to make TLS connection without CA file for verifying other Node", address);
+                return None;
+            }
+        };
+
+        let name = if self.server_name.is_empty() {
+            match SocketAddr::from_str(address) {
+                Ok(a) => ServerName::IpAddress(a.ip().into()),
+                Err(e) => {
+                    Log::error("Unable to parse address for TLS server name", e.to_string().as_str());
+                    return None;
+                }
+            }
+        } else {
+            match ServerName::try_from(self.server_name.clone()) {
+                Ok(n) => n,
+                Err(e) => {
+                    Log::error("Invalid TLS server name", e.to_string().as_str());
+                    return None;
+                }
+            }
+        };
+
+        match ClientConnection::new(config, name) {
+            Ok(c) => Some(TcpTls::unlimited(Connection::Client(c))),
+            Err(e) => {
+                Log::error("Unable to make TLS client connection", e.to_string().as_str());
+                None
+            }
+        }
+    }
+
+    /// Write queue of TcpConnection is not limited
+    /// so TLS session shouldn't limit it as well
+    fn unlimited(mut session: Connection) -> Connection {
+        session.set_buffer_limit(None);
+        session
+    }
+
+    #[inline(always)]
+    pub fn bind_token(&self) -> bool {
+        self.bind_token
+    }
+
+    /// Checking if peer certificate of TLS session is issued for given token
+    /// Token should be one of the DNS names in certificate Subject Alternative Names
+    pub fn peer_has_name(session: &Connection, token: &str) -> bool {
+        let cert = match session.peer_certificates().and_then(|certs| certs.first()) {
+            Some(c) => c,
+            None => return false
+        };
+
+        let name = match ServerName::try_from(token) {
+            Ok(n) => n,
+            Err(_) => return false
+        };
+
+        match webpki::EndEntityCert::try_from(cert) {
+            Ok(c) => c.verify_is_valid_for_subject_name(&name).is_ok(),
+            Err(_) => false
+        }
+    }
+
+    fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, Error> {
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::Io(format!("Unable to open TLS certificates file {}", path), e))
+        };
+
+        let certs: Result<Vec<CertificateDer<'static>>, _> = rustls_pemfile::certs(&mut BufReader::new(file)).collect();
+        match certs {
+            Ok(ref c) if c.is_empty() => Err(Error::Config(format!("There is no certificates in {}", path))),
+            Ok(c) => Ok(c),
+            Err(e) => Err(Error::Config(format!("Unable to parse TLS certificates file {} -> {}", path, e)))
+        }
+    }
+
+    fn load_key(path: &str) -> Result<PrivateKeyDer<'static>, Error> {
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::Io(format!("Unable to open TLS key file {}", path), e))
+        };
+
+        match rustls_pemfile::private_key(&mut BufReader::new(file)) {
+            Ok(Some(k)) => Ok(k),
+            Ok(None) => Err(Error::Config(format!("There is no private key in {}", path))),
+            Err(e) => Err(Error::Config(format!("Unable to parse TLS key file {} -> {}", path, e)))
+        }
+    }
+}
@@ -0,0 +1,114 @@
+#![allow(dead_code)]
+extern crate mio;
+
+use helper::Log;
+
+use self::mio::{Ready, PollOpt, Token};
+
+use node::{Node, NET_UNIX_SERVER_TOKEN};
+use error::Error;
+use network::{TcpConnection, TcpNetwork, Networking
+              , TcpHandlerCommand, TcpHandlerCMD
+              , Stream, UnixStream, UnixListener};
+
+use std::io::ErrorKind;
+use std::sync::Arc;
+
+/// UnixNetwork Trait for accepting local connections over Unix domain socket
+/// Accepted connections are handled by TCP handlers, because framing is the same
+pub trait UnixNetwork {
+    /// Registering Unix socket listener to POLL service, if it's configured
+    fn register_unix(&mut self) -> Result<(), Error>;
+
+    /// Make Unix socket listener from given socket file path
+    fn make_unix_server(path: &str, mode: u32) -> Result<Option<UnixListener>, Error>;
+
+    /// Handler for event loop ready event
+    /// If event token is not for Unix socket listener it will return false
+    fn unix_ready(&mut self, token: Token, event_kind: Ready) -> bool;
+
+    /// Function for accepting Unix socket connections
+    fn unix_acceptable(&mut self);
+
+    /// Transferring accepted connection to one of the TCP handlers
+    fn unix_transfer_connection(&mut self, sock: UnixStream);
+}
+
+impl UnixNetwork for Node {
+    fn register_unix(&mut self) -> Result<(), Error> {
+        let server = match self.net_unix_server {
+            Some(ref s) => s,
+            None => return Ok(())
+        };
+
+        match self.poll.register(server, NET_UNIX_SERVER_TOKEN, Ready::readable(), PollOpt::edge()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::io("Unable to register Unix socket server to Node POLL service", e))
+        }
+    }
+
+    fn make_unix_server(path: &str, mode: u32) -> Result<Option<UnixListener>, Error> {
+        if path.is_empty() {
+            return Ok(None);
+        }
+
+        match UnixListener::bind(path, mode) {
+            Ok(s) => Ok(Some(s)),
+            Err(e) => Err(Error::Io(format!("Unable to bind given Unix socket path {}", path), e))
+        }
+    }
+
+    #[inline(always)]
+    fn unix_ready(&mut self, token: Token, event_kind: Ready) -> bool {
+        if token == NET_UNIX_SERVER_TOKEN {
+            if event_kind != Ready::readable() {
+                Log::error("Unexpected Unix socket Server event kind", "Ignoring for now!");
+                return false;
+            }
+
+            self.unix_acceptable();
+            return true;
+        }
+
+        false
+    }
+
+    #[inline(always)]
+    fn unix_acceptable(&mut self) {
+        loop {
+            let sock = {
+                let server = match self.net_unix_server {
+                    Some(ref s) => s,
+                    None => return
+                };
+
+                match server.accept() {
+                    Ok(s) => s,
+                    Err(e) => {
+                        // if we got WouldBlock, then this is Non Blocking socket
+                        // and data still not available for this, so it's not a connection error
+                        if e.kind() != ErrorKind::WouldBlock {
+                            Log::error("Unable to accept connection from Unix socket server", e.to_string().as_str());
+                        }
+                        return;
+                    }
+                }
+            };
+
+            self.unix_transfer_connection(sock);
+        }
+    }
+
+    #[inline(always)]
+    fn unix_transfer_connection(&mut self, sock: UnixStream) {
+        let mut command = TcpHandlerCommand::new();
+        command.cmd = TcpHandlerCMD::HandleConnection;
+        // local connections are protected by socket file permissions, so TLS is not used for them
+        command.conn.push(TcpConnection::new(Stream::Unix(sock), Token(0), true));
+        // adding handshake info, for writing it later from handler
+        command.conn[0].add_handshake(Arc::new(self.handshake_info()));
+        if let Err(e) = self.tcp_get_handler().send(command) {
+            Log::error("Unable to send HandleConnection command to TCP handler", e.to_string().as_str());
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod main;
+mod socket;
+
+pub use self::main::UnixNetwork;
+pub use self::socket::{UnixStream, UnixListener};
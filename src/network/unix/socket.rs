@@ -0,0 +1,115 @@
+#![allow(dead_code)]
+extern crate mio;
+
+use self::mio::{Evented, Poll, Token, Ready, PollOpt};
+use self::mio::unix::EventedFd;
+
+use std::io::{self, Read, Write};
+use std::fs;
+use std::net::Shutdown;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net;
+use std::path::Path;
+
+/// Non blocking Unix domain socket stream registrable to POLL service
+pub struct UnixStream {
+    inner: net::UnixStream
+}
+
+/// Non blocking Unix domain socket listener registrable to POLL service
+pub struct UnixListener {
+    inner: net::UnixListener,
+    // socket file path, for removing it when listener is dropped
+    path: String
+}
+
+impl UnixStream {
+    #[inline(always)]
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+}
+
+impl Read for UnixStream {
+    #[inline(always)]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for UnixStream {
+    #[inline(always)]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    #[inline(always)]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Evented for UnixStream {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        EventedFd(&self.inner.as_raw_fd()).register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        EventedFd(&self.inner.as_raw_fd()).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        EventedFd(&self.inner.as_raw_fd()).deregister(poll)
+    }
+}
+
+impl UnixListener {
+    /// Binding to given socket file path
+    /// Old socket file is removed, because it's probably left from previous process
+    /// If mode is not 0, socket file permissions are set to it
+    pub fn bind(path: &str, mode: u32) -> io::Result<UnixListener> {
+        if Path::new(path).exists() {
+            fs::remove_file(path)?;
+        }
+
+        let inner = net::UnixListener::bind(path)?;
+        inner.set_nonblocking(true)?;
+
+        if mode != 0 {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+
+        Ok(UnixListener {
+            inner,
+            path: String::from(path)
+        })
+    }
+
+    /// Accepting connection from listener as a non blocking stream
+    pub fn accept(&self) -> io::Result<UnixStream> {
+        let (inner, _) = self.inner.accept()?;
+        inner.set_nonblocking(true)?;
+        Ok(UnixStream { inner })
+    }
+}
+
+impl Drop for UnixListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Evented for UnixListener {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        EventedFd(&self.inner.as_raw_fd()).register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        EventedFd(&self.inner.as_raw_fd()).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        EventedFd(&self.inner.as_raw_fd()).deregister(poll)
+    }
+}
@@ -0,0 +1,127 @@
+#![allow(dead_code)]
+extern crate mio;
+
+use helper::Log;
+
+use self::mio::tcp::{TcpListener, TcpStream};
+use self::mio::{Ready, PollOpt, Token};
+
+use node::{Node, NET_WS_SERVER_TOKEN};
+use error::Error;
+use network::{TcpConnection, TcpNetwork, Networking
+              , TcpHandlerCommand, TcpHandlerCMD
+              , Stream, WsStream};
+
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::io::ErrorKind;
+use std::sync::Arc;
+
+/// WsNetwork Trait for accepting WebSocket API clients
+/// Accepted connections are handled by TCP handlers, WebSocket framing is converted by WsStream
+pub trait WsNetwork {
+    /// Registering WebSocket server to POLL service, if it's configured
+    /// WebSocket connections are plain TCP without certificates, so server is not started with TLS token binding
+    fn register_ws(&mut self) -> Result<(), Error>;
+
+    /// Make WebSocket server listener from given address
+    fn make_ws_server(address: &str) -> Result<Option<TcpListener>, Error>;
+
+    /// Handler for event loop ready event
+    /// If event token is not for WebSocket server it will return false
+    fn ws_ready(&mut self, token: Token, event_kind: Ready) -> bool;
+
+    /// Function for accepting WebSocket connections
+    fn ws_acceptable(&mut self);
+
+    /// Transferring accepted connection to one of the TCP handlers
+    fn ws_transfer_connection(&mut self, sock: TcpStream);
+}
+
+impl WsNetwork for Node {
+    fn register_ws(&mut self) -> Result<(), Error> {
+        let server = match self.net_ws_server {
+            Some(ref s) => s,
+            None => return Ok(())
+        };
+
+        if self.net_tls.as_ref().map(|t| t.bind_token()).unwrap_or(false) {
+            return Err(Error::Config(String::from("WebSocket server can't be used with TLS token binding, its clients have no certificates")));
+        }
+
+        match self.poll.register(server, NET_WS_SERVER_TOKEN, Ready::readable(), PollOpt::edge()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::io("Unable to register WebSocket server to Node POLL service", e))
+        }
+    }
+
+    fn make_ws_server(address: &str) -> Result<Option<TcpListener>, Error> {
+        if address.is_empty() {
+            return Ok(None);
+        }
+
+        let addr = match SocketAddr::from_str(address) {
+            Ok(a) => a,
+            Err(e) => return Err(Error::Config(format!("Unable to parse given WebSocket server address {} -> {}", address, e)))
+        };
+
+        match TcpListener::bind(&addr) {
+            Ok(s) => Ok(Some(s)),
+            Err(e) => Err(Error::Io(format!("Unable to bind given WebSocket server address {}", address), e))
+        }
+    }
+
+    #[inline(always)]
+    fn ws_ready(&mut self, token: Token, event_kind: Ready) -> bool {
+        if token == NET_WS_SERVER_TOKEN {
+            if event_kind != Ready::readable() {
+                Log::error("Unexpected WebSocket Server event kind", "Ignoring for now!");
+                return false;
+            }
+
+            self.ws_acceptable();
+            return true;
+        }
+
+        false
+    }
+
+    #[inline(always)]
+    fn ws_acceptable(&mut self) {
+        loop {
+            let sock = {
+                let server = match self.net_ws_server {
+                    Some(ref s) => s,
+                    None => return
+                };
+
+                match server.accept() {
+                    Ok((s, _)) => s,
+                    Err(e) => {
+                        // if we got WouldBlock, then this is Non Blocking socket
+                        // and data still not available for this, so it's not a connection error
+                        if e.kind() != ErrorKind::WouldBlock {
+                            Log::error("Unable to accept connection from WebSocket server", e.to_string().as_str());
+                        }
+                        return;
+                    }
+                }
+            };
+
+            self.ws_transfer_connection(sock);
+        }
+    }
+
+    #[inline(always)]
+    fn ws_transfer_connection(&mut self, sock: TcpStream) {
+        let stream = WsStream::new(sock, self.net_config.max_event_frame, &self.net_config.ws_origins);
+        let mut command = TcpHandlerCommand::new();
+        command.cmd = TcpHandlerCMD::HandleConnection;
+        command.conn.push(TcpConnection::new(Stream::Ws(stream), Token(0), true));
+        // adding handshake info, for writing it later from handler
+        command.conn[0].add_handshake(Arc::new(self.handshake_info()));
+        if let Err(e) = self.tcp_get_handler().send(command) {
+            Log::error("Unable to send HandleConnection command to TCP handler", e.to_string().as_str());
+        }
+    }
+}
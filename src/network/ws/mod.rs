@@ -0,0 +1,5 @@
+mod main;
+mod stream;
+
+pub use self::main::WsNetwork;
+pub use self::stream::WsStream;
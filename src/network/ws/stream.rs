@@ -0,0 +1,513 @@
+#![allow(dead_code)]
+extern crate mio;
+extern crate sha1;
+extern crate base64;
+
+use self::mio::{Evented, Poll, Token, Ready, PollOpt};
+use self::mio::tcp::TcpStream;
+use self::sha1::{Sha1, Digest};
+use self::base64::Engine;
+use self::base64::engine::general_purpose::STANDARD as BASE64;
+
+use network::tcp::{TCP_FRAME_PING, TCP_FRAME_PONG};
+use network::LEGACY_API_VERSION;
+use helper::{Log, NetHelper};
+use event::{Event, EventCodec, Compression, EVENT_VERSION_HEADER};
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::Shutdown;
+use std::cmp;
+use std::str;
+
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const WS_MAX_HTTP_REQUEST: usize = 8 * 1024;
+
+const WS_OP_CONTINUATION: u8 = 0x0;
+const WS_OP_TEXT: u8 = 0x1;
+const WS_OP_BINARY: u8 = 0x2;
+const WS_OP_CLOSE: u8 = 0x8;
+const WS_OP_PING: u8 = 0x9;
+const WS_OP_PONG: u8 = 0xA;
+
+/// Close frame status code for protocol errors
+const WS_CLOSE_PROTOCOL_ERROR: u16 = 1002;
+
+/// WebSocket stream, which is converting WebSocket messages to our length prefixed frames and back
+/// so that connection would be handled by TCP handlers without knowing about WebSocket protocol
+/// Handshake is made from HTTP upgrade request, token is given as a "token" query parameter
+/// and "format=json" query parameter is making Node to send events as a JSON text messages
+pub struct WsStream {
+    socket: TcpStream,
+    // max length of a single WebSocket message
+    max_message: usize,
+    // comma separated origins allowed to make upgrade request, empty means any origin is allowed
+    origins: String,
+
+    // HTTP upgrade is done, and we are reading WebSocket frames
+    upgraded: bool,
+    // sending events as a JSON text messages instead of binary ones
+    json: bool,
+    // close frame is received, or sent by us
+    closed: bool,
+
+    // bytes from socket, which are not parsed yet
+    raw: Vec<u8>,
+    // fragmented message data and opcode of the first fragment
+    message: Vec<u8>,
+    message_opcode: u8,
+
+    // converted data ready to be read by connection
+    input: Vec<u8>,
+    input_index: usize,
+
+    // data written by connection, which is not a full frame yet
+    frames: Vec<u8>,
+    // Node handshake is not needed for WebSocket clients, so it's skipped
+    skip_handshake: bool,
+
+    // WebSocket frames waiting to be written to socket
+    output: Vec<u8>,
+    output_index: usize,
+}
+
+impl WsStream {
+    pub fn new(socket: TcpStream, max_message: usize, origins: &str) -> WsStream {
+        WsStream {
+            socket,
+            max_message,
+            origins: String::from(origins),
+            upgraded: false,
+            json: false,
+            closed: false,
+            raw: vec![],
+            message: vec![],
+            message_opcode: WS_OP_BINARY,
+            input: vec![],
+            input_index: 0,
+            frames: vec![],
+            skip_handshake: true,
+            output: vec![],
+            output_index: 0
+        }
+    }
+
+    #[inline(always)]
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.socket.shutdown(how)
+    }
+
+    /// We have WebSocket frames which are not written to socket yet
+    #[inline(always)]
+    pub fn wants_write(&self) -> bool {
+        self.output_index < self.output.len()
+    }
+
+    /// Reading from socket until we have some converted data, or socket is empty
+    fn fill_input(&mut self) -> io::Result<usize> {
+        let mut buffer = [0; 16 * 1024];
+        while self.input_index >= self.input.len() {
+            if self.closed {
+                return Ok(0);
+            }
+
+            let n = self.socket.read(&mut buffer)?;
+            if n == 0 {
+                return Ok(0);
+            }
+
+            self.raw.extend_from_slice(&buffer[..n]);
+            if !self.upgraded {
+                self.read_upgrade()?;
+            }
+
+            if self.upgraded {
+                self.read_frames()?;
+            }
+        }
+
+        Ok(self.input.len() - self.input_index)
+    }
+
+    /// Parsing HTTP upgrade request and answering with handshake response
+    /// After that giving our handshake to connection, as it would be sent by regular API client
+    fn read_upgrade(&mut self) -> io::Result<()> {
+        let end = match self.raw.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(p) => p + 4,
+            None => {
+                if self.raw.len() > WS_MAX_HTTP_REQUEST {
+                    return Err(WsStream::invalid("Too large WebSocket upgrade request"));
+                }
+                return Ok(());
+            }
+        };
+
+        let request = String::from_utf8_lossy(&self.raw[..end]).into_owned();
+        self.raw.drain(..end);
+
+        let (token, key) = match WsStream::parse_upgrade(&request) {
+            Ok((token, key, json, origin)) => {
+                // browsers are always sending Origin, so web pages from other sites couldn't use our Node
+                if !origin.is_empty() && !self.origin_allowed(&origin) {
+                    self.output.extend_from_slice(b"HTTP/1.1 403 Forbidden\r\nConnection: close\r\n\r\n");
+                    let _ = self.flush_output();
+                    return Err(WsStream::invalid("WebSocket upgrade request from not allowed origin"));
+                }
+
+                self.json = json;
+                (token, key)
+            }
+            Err(e) => {
+                self.output.extend_from_slice(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n");
+                let _ = self.flush_output();
+                return Err(WsStream::invalid(e));
+            }
+        };
+
+        let mut hasher = Sha1::new();
+        hasher.update(key.as_bytes());
+        hasher.update(WS_GUID.as_bytes());
+        let accept = BASE64.encode(hasher.finalize());
+        self.output.extend_from_slice(format!("HTTP/1.1 101 Switching Protocols\r\n\
+                                               Upgrade: websocket\r\n\
+                                               Connection: Upgrade\r\n\
+                                               Sec-WebSocket-Accept: {}\r\n\r\n", accept).as_bytes());
+
+        // API version, token and 0 as a value, because WebSocket clients could be only API clients
+        // legacy API version is used, because WebSocket clients are not exchanging capabilities
+        let token_len = token.len();
+        let mut handshake = vec![0; 4 + 4 + token_len + 8];
+        let mut offset = NetHelper::u32_to_bytes(LEGACY_API_VERSION, &mut handshake, 0);
+        offset += NetHelper::u32_to_bytes((token_len + 8) as u32, &mut handshake, offset);
+        handshake[offset..offset + token_len].copy_from_slice(token.as_bytes());
+        self.input.extend_from_slice(&handshake);
+        self.upgraded = true;
+        Ok(())
+    }
+
+    /// Checking if given Origin header value is in the list of allowed ones
+    fn origin_allowed(&self, origin: &str) -> bool {
+        self.origins.is_empty() || self.origins.split(',').any(|o| o.trim().eq_ignore_ascii_case(origin))
+    }
+
+    /// Getting token, Sec-WebSocket-Key, JSON format flag and Origin from HTTP upgrade request
+    fn parse_upgrade(request: &str) -> Result<(String, String, bool, String), &'static str> {
+        let mut lines = request.split("\r\n");
+        let target = match lines.next().map(|l| l.split(' ').collect::<Vec<&str>>()) {
+            Some(ref parts) if parts.len() == 3 && parts[0] == "GET" => String::from(parts[1]),
+            _ => return Err("Invalid WebSocket upgrade request line")
+        };
+
+        let mut upgrade = false;
+        let mut key = String::new();
+        let mut origin = String::new();
+        for line in lines {
+            let (name, value) = match line.find(':') {
+                Some(i) => (line[..i].trim().to_lowercase(), line[i + 1..].trim()),
+                None => continue
+            };
+
+            match name.as_str() {
+                "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+                "sec-websocket-key" => key = String::from(value),
+                "origin" => origin = String::from(value),
+                _ => {}
+            }
+        }
+
+        if !upgrade || key.is_empty() {
+            return Err("Request is not a WebSocket upgrade");
+        }
+
+        let mut token = String::new();
+        let mut json = false;
+        if let Some(i) = target.find('?') {
+            for param in target[i + 1..].split('&') {
+                let mut kv = param.splitn(2, '=');
+                match (kv.next(), kv.next()) {
+                    (Some("token"), Some(v)) => token = WsStream::percent_decode(v),
+                    (Some("format"), Some("json")) => json = true,
+                    _ => {}
+                }
+            }
+        }
+
+        if token.is_empty() {
+            return Err("WebSocket upgrade request without token");
+        }
+
+        Ok((token, key, json, origin))
+    }
+
+    fn percent_decode(value: &str) -> String {
+        let bytes = value.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'%' if i + 2 < bytes.len() => {
+                    match str::from_utf8(&bytes[i + 1..i + 3]).map(|h| u8::from_str_radix(h, 16)) {
+                        Ok(Ok(b)) => {
+                            out.push(b);
+                            i += 3;
+                            continue;
+                        }
+                        _ => out.push(b'%')
+                    }
+                }
+                b'+' => out.push(b' '),
+                b => out.push(b)
+            }
+            i += 1;
+        }
+
+        String::from_utf8_lossy(&out).into_owned()
+    }
+
+    /// Parsing all complete WebSocket frames which we have in raw buffer
+    fn read_frames(&mut self) -> io::Result<()> {
+        loop {
+            if self.raw.len() < 2 {
+                return Ok(());
+            }
+
+            let fin = self.raw[0] & 0x80 != 0;
+            let opcode = self.raw[0] & 0x0F;
+            // clients should mask every frame, otherwise connection should be closed (RFC 6455 section 5.1)
+            if self.raw[1] & 0x80 == 0 {
+                self.write_frame(WS_OP_CLOSE, &WS_CLOSE_PROTOCOL_ERROR.to_be_bytes());
+                self.closed = true;
+                let _ = self.flush_output();
+                return Err(WsStream::invalid("Unmasked WebSocket frame from client"));
+            }
+            let (len, mut offset) = match self.raw[1] & 0x7F {
+                126 => {
+                    if self.raw.len() < 4 {
+                        return Ok(());
+                    }
+                    ((u64::from(self.raw[2]) << 8) | u64::from(self.raw[3]), 4)
+                }
+                127 => {
+                    if self.raw.len() < 10 {
+                        return Ok(());
+                    }
+                    (self.raw[2..10].iter().fold(0u64, |n, b| (n << 8) | u64::from(*b)), 10)
+                }
+                n => (u64::from(n), 2)
+            };
+
+            if len as usize > self.max_message || self.message.len() + len as usize > self.max_message {
+                return Err(WsStream::invalid("Too large WebSocket message"));
+            }
+
+            let len = len as usize;
+            let mask_offset = offset;
+            offset += 4;
+
+            if self.raw.len() < offset + len {
+                return Ok(());
+            }
+
+            let mut payload: Vec<u8> = self.raw[offset..offset + len].to_vec();
+            let mask = [self.raw[mask_offset], self.raw[mask_offset + 1], self.raw[mask_offset + 2], self.raw[mask_offset + 3]];
+            for (i, b) in payload.iter_mut().enumerate() {
+                *b ^= mask[i % 4];
+            }
+            self.raw.drain(..offset + len);
+
+            match opcode {
+                WS_OP_PING => self.write_frame(WS_OP_PONG, &payload),
+                // other side is answering to our heartbeat
+                WS_OP_PONG => self.input.extend_from_slice(&[0, 0, 0, 1, TCP_FRAME_PONG]),
+                WS_OP_CLOSE => {
+                    if !self.closed {
+                        self.write_frame(WS_OP_CLOSE, &payload);
+                        self.closed = true;
+                    }
+                    return Ok(());
+                }
+                WS_OP_CONTINUATION | WS_OP_TEXT | WS_OP_BINARY => {
+                    if opcode != WS_OP_CONTINUATION {
+                        self.message_opcode = opcode;
+                    }
+
+                    self.message.extend_from_slice(&payload);
+                    if fin {
+                        self.read_message()?;
+                    }
+                }
+                _ => return Err(WsStream::invalid("Unknown WebSocket frame opcode"))
+            }
+        }
+    }
+
+    /// Converting complete WebSocket message to our length prefixed frame
+    fn read_message(&mut self) -> io::Result<()> {
+        let message = self.message.split_off(0);
+        let data = if self.message_opcode == WS_OP_TEXT {
+            let text = match String::from_utf8(message) {
+                Ok(t) => t,
+                Err(_) => return Err(WsStream::invalid("WebSocket text message is not valid UTF-8"))
+            };
+
+            match EventCodec::from_json(&text).map(|e| e.to_raw_with(EVENT_VERSION_HEADER, Compression::None)) {
+                // raw event is already length prefixed
+                Ok(Some(raw)) => {
+                    self.input.extend_from_slice(&raw);
+                    return Ok(());
+                }
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    Log::warn("Unable to parse JSON event from WebSocket client", e.to_string().as_str());
+                    return Ok(());
+                }
+            }
+        } else {
+            message
+        };
+
+        let mut len = vec![0; 4];
+        NetHelper::u32_to_bytes(data.len() as u32, &mut len, 0);
+        self.input.extend_from_slice(&len);
+        self.input.extend_from_slice(&data);
+        Ok(())
+    }
+
+    /// Converting all complete length prefixed frames written by connection into WebSocket messages
+    fn write_frames(&mut self) {
+        loop {
+            let len = match NetHelper::bytes_to_u32(&self.frames, if self.skip_handshake { 4 } else { 0 }) {
+                Ok(l) => l as usize,
+                Err(_) => return
+            };
+            let header = if self.skip_handshake { 8 } else { 4 };
+            if self.frames.len() < header + len {
+                return;
+            }
+
+            let frame: Vec<u8> = self.frames.drain(..header + len).skip(header).collect();
+            if self.skip_handshake {
+                self.skip_handshake = false;
+                continue;
+            }
+
+            if frame.len() == 1 {
+                match frame[0] {
+                    TCP_FRAME_PING => self.write_frame(WS_OP_PING, &[]),
+                    TCP_FRAME_PONG => self.write_frame(WS_OP_PONG, &[]),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if !self.json {
+                self.write_frame(WS_OP_BINARY, &frame);
+                continue;
+            }
+
+            match Event::from_raw(&frame) {
+                Ok(event) => {
+                    let text = EventCodec::to_json(&event);
+                    self.write_frame(WS_OP_TEXT, text.as_bytes());
+                }
+                Err(e) => Log::warn("Unable to convert event to JSON for WebSocket client", e.to_string().as_str())
+            }
+        }
+    }
+
+    /// Adding server side WebSocket frame to output, server frames are not masked
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) {
+        let len = payload.len();
+        self.output.push(0x80 | opcode);
+        if len < 126 {
+            self.output.push(len as u8);
+        } else if len <= 0xFFFF {
+            self.output.push(126);
+            self.output.extend_from_slice(&[(len >> 8) as u8, len as u8]);
+        } else {
+            self.output.push(127);
+            for i in (0..8).rev() {
+                self.output.push((len as u64 >> (i * 8)) as u8);
+            }
+        }
+        self.output.extend_from_slice(payload);
+    }
+
+    /// Writing as much output as we can to socket
+    fn flush_output(&mut self) -> io::Result<()> {
+        while self.output_index < self.output.len() {
+            let n = self.socket.write(&self.output[self.output_index..])?;
+            if n == 0 {
+                return Err(io::Error::new(ErrorKind::WriteZero, "Unable to write WebSocket data"));
+            }
+            self.output_index += n;
+        }
+
+        self.output.clear();
+        self.output_index = 0;
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn invalid(msg: &str) -> io::Error {
+        io::Error::new(ErrorKind::InvalidData, msg)
+    }
+}
+
+impl Read for WsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = self.fill_input();
+
+        // answers to pings and close frames should be sent right away
+        match self.flush_output() {
+            Ok(_) => {}
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e)
+        }
+
+        if result? == 0 {
+            return Ok(0);
+        }
+
+        let n = cmp::min(buf.len(), self.input.len() - self.input_index);
+        buf[..n].copy_from_slice(&self.input[self.input_index..self.input_index + n]);
+        self.input_index += n;
+        if self.input_index >= self.input.len() {
+            self.input.clear();
+            self.input_index = 0;
+        }
+
+        Ok(n)
+    }
+}
+
+impl Write for WsStream {
+    /// Everything is converted into WebSocket frames, so data is always accepted
+    /// Frames which are not written to socket are sent during flush
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.frames.extend_from_slice(buf);
+        self.write_frames();
+        match self.flush_output() {
+            Ok(_) => Ok(buf.len()),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => Ok(buf.len()),
+            Err(e) => Err(e)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_output()
+    }
+}
+
+impl Evented for WsStream {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.socket.register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.socket.reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        self.socket.deregister(poll)
+    }
+}
@@ -0,0 +1,191 @@
+#![allow(dead_code)]
+extern crate mio;
+
+use self::mio::channel::Sender;
+
+use node::Node;
+use config::NodeConfig;
+use event::{Event, EventHandler, EventCommand, EventCMD};
+use error::Error;
+
+use std::io;
+use std::thread;
+use std::sync::mpsc;
+
+/// Callback given to the builder, it should be Send for running Node on a separate thread
+type BuilderCallback = Box<dyn Fn(&Event, &mut Node) -> bool + Send>;
+
+/// Builder for embedding Node inside other applications
+/// Node could be started as a blocking event loop with "run",
+/// on a separate thread with "spawn", or handled step by step after "build" with "Node::poll_once"
+pub struct NodeBuilder {
+    config: NodeConfig,
+    // local event callbacks, which are added to Node before starting it
+    callbacks: Vec<(String, BuilderCallback)>
+}
+
+/// Handle for Node running on a separate thread
+pub struct NodeHandle {
+    token: String,
+    // channel for sending events and commands to Node event loop
+    sender: Sender<EventCommand>,
+    thread: thread::JoinHandle<Result<(), Error>>
+}
+
+impl NodeBuilder {
+    /// Making builder with default configuration
+    pub fn new() -> NodeBuilder {
+        NodeBuilder::from_config(NodeConfig::default())
+    }
+
+    /// Making builder from existing configuration, for example parsed from command line
+    pub fn from_config(config: NodeConfig) -> NodeBuilder {
+        NodeBuilder {
+            config,
+            callbacks: vec![]
+        }
+    }
+
+    pub fn token(mut self, token: &str) -> NodeBuilder {
+        self.config.token = String::from(token);
+        self
+    }
+
+    pub fn value(mut self, value: u64) -> NodeBuilder {
+        self.config.value = value;
+        self
+    }
+
+    pub fn api_version(mut self, version: u32) -> NodeBuilder {
+        self.config.api_version = version;
+        self
+    }
+
+    /// TCP server address, like "0.0.0.0:8000"
+    pub fn tcp_host(mut self, address: &str) -> NodeBuilder {
+        self.config.network.tcp_server_host = String::from(address);
+        self
+    }
+
+    /// Parent Node address to connect after start
+    pub fn parent(mut self, address: &str) -> NodeBuilder {
+        self.config.parent_address = String::from(address);
+        self
+    }
+
+    /// Count of TCP handler threads, 0 means CPU count
+    pub fn concurrency(mut self, count: usize) -> NodeBuilder {
+        self.config.network.concurrency = count;
+        self
+    }
+
+    pub fn unix_socket(mut self, path: &str) -> NodeBuilder {
+        self.config.network.unix_socket_path = String::from(path);
+        self
+    }
+
+    pub fn ws_host(mut self, address: &str) -> NodeBuilder {
+        self.config.network.ws_server_host = String::from(address);
+        self
+    }
+
+    /// Getting configuration for changing options which don't have their own builder function
+    #[inline(always)]
+    pub fn config_mut(&mut self) -> &mut NodeConfig {
+        &mut self.config
+    }
+
+    /// Adding local callback for events with given name, which could have wildcards
+    pub fn on<F>(mut self, name: &str, callback: F) -> NodeBuilder
+        where F: Fn(&Event, &mut Node) -> bool + Send + 'static {
+        self.callbacks.push((String::from(name), Box::new(callback)));
+        self
+    }
+
+    /// Making Node with started services, without running its event loop
+    pub fn build(self) -> Result<Node, Error> {
+        let mut node = Node::new(&self.config)?;
+        for (name, callback) in self.callbacks {
+            node.on(name.as_str(), callback);
+        }
+
+        node.init()?;
+        Ok(node)
+    }
+
+    /// Running Node event loop on current thread until it's stopped
+    pub fn run(self) -> Result<(), Error> {
+        self.build()?.run()
+    }
+
+    /// Running Node event loop on a separate thread
+    /// Returns after Node services are started, so that errors are given from here
+    pub fn spawn(self) -> Result<NodeHandle, Error> {
+        let (init_s, init_r) = mpsc::channel::<Result<(String, Sender<EventCommand>), Error>>();
+        let thread = thread::spawn(move || {
+            let mut node = match self.build() {
+                Ok(n) => n,
+                Err(e) => {
+                    let _ = init_s.send(Err(e));
+                    return Ok(());
+                }
+            };
+
+            let _ = init_s.send(Ok((node.token.clone(), node.event_sender_chan.clone())));
+            node.run()
+        });
+
+        match init_r.recv() {
+            Ok(Ok((token, sender))) => Ok(NodeHandle {
+                token,
+                sender,
+                thread
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(Error::io("Unable to start Node", io::Error::new(io::ErrorKind::BrokenPipe, "Node thread exited before starting services")))
+        }
+    }
+}
+
+impl Default for NodeBuilder {
+    fn default() -> NodeBuilder {
+        NodeBuilder::new()
+    }
+}
+
+impl NodeHandle {
+    #[inline(always)]
+    pub fn token(&self) -> &str {
+        self.token.as_str()
+    }
+
+    /// Running local callbacks of Node with given event
+    pub fn trigger(&self, event: Event) -> Result<(), Error> {
+        let mut command = EventCommand::new();
+        command.cmd = EventCMD::HandleEvent;
+        command.event.push(event);
+        self.send(command)
+    }
+
+    /// Stopping Node event loop, without waiting for it
+    pub fn stop(&self) -> Result<(), Error> {
+        let mut command = EventCommand::new();
+        command.cmd = EventCMD::Stop;
+        self.send(command)
+    }
+
+    /// Waiting until Node event loop is stopped
+    pub fn join(self) -> Result<(), Error> {
+        match self.thread.join() {
+            Ok(r) => r,
+            Err(_) => Err(Error::io("Unable to stop Node", io::Error::other("Node thread panicked")))
+        }
+    }
+
+    fn send(&self, command: EventCommand) -> Result<(), Error> {
+        match self.sender.send(command) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::io("Unable to send command to Node", io::Error::new(io::ErrorKind::BrokenPipe, e.to_string())))
+        }
+    }
+}
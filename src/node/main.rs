@@ -2,22 +2,30 @@
 extern crate mio;
 extern crate num_cpus;
 extern crate uuid;
+extern crate rand;
 
-use self::mio::{Poll, Events};
+use self::mio::{Poll, Events, Ready, PollOpt};
+use self::mio::timer::Timer;
 use self::mio::channel::{channel, Sender, Receiver};
 use self::mio::tcp::{TcpListener};
 
 use network::{NetworkCommand, Connection
-              , TcpHandlerCommand, TcpNetwork, Networking
-              , Slab, TcpConnection, CONNECTION_COUNT_PRE_ALLOC};
-use config::NodeConfig;
-use helper::Log;
-use node::{EVENT_LOOP_EVENTS_SIZE, DEFAULT_API_VERSION};
-use event::Event;
+              , TcpHandlerCommand, TcpHandlerCMD, TcpNetwork, Networking
+              , Slab, TcpConnection, TcpTls, UnixNetwork, UnixListener, WsNetwork, CONNECTION_COUNT_PRE_ALLOC};
+use config::{NodeConfig, NetworkingConfig};
+use helper::{Log, Path, NameTrie};
+use node::{NodeTimeout, EVENT_LOOP_EVENTS_SIZE, DEFAULT_API_VERSION, NODE_TIMER_TOKEN, EVENT_RECEIVER_CHANNEL_TOKEN};
+use event::{Event, RecentEvents, DroppedEvents, EventHandler, EventCallback, EventCommand};
+use graph::{Graph, Gossip, GRAPH_EVENT_PREFIX};
+use api::{ApiControl, Balancer, API_EVENT_PREFIX};
+use queue::{EventQueue, Queueing};
+use auth::{Authenticator, NodeAuthenticator, make_authenticator, make_node_authenticator};
+use error::Error;
 
 use std::collections::BTreeMap;
-use std::process;
-use std::error::Error;
+use std::time::Duration;
+use std::cmp;
+use std::sync::Arc;
 
 pub struct Node {
     /// Node Valid information for identification
@@ -36,89 +44,296 @@ pub struct Node {
     pub net_tcp_handler_index: usize,
     // TCP server socket
     pub net_tcp_server: TcpListener,
+    // Unix socket server for local connections, if it's configured
+    pub net_unix_server: Option<UnixListener>,
+    // WebSocket server for browser and mobile API clients, if it's configured
+    pub net_ws_server: Option<TcpListener>,
     // keeping just a simple TcpConnection as a pending connection
     pub net_tcp_pending_connections: Slab<TcpConnection>,
+    // networking configuration shared with TCP handlers
+    pub net_config: NetworkingConfig,
+    // TLS configuration for TCP connections, if TLS is enabled
+    pub net_tls: Option<TcpTls>,
+    // API client authentication used by TCP handlers
+    pub net_authenticator: Option<Arc<dyn Authenticator>>,
+    // Node challenge-response authentication used by TCP handlers
+    pub net_node_authenticator: Option<Arc<NodeAuthenticator>>,
+    // IDs of recently handled events, for dropping the ones coming back because of routing loops
+    pub net_recent_events: RecentEvents,
+    // counters of events dropped instead of forwarding them
+    pub net_dropped_events: DroppedEvents,
 
     /// POLL service for this node thread event loop
     pub poll: Poll,
+    // events buffer for POLL service, it's taken during events handling
+    poll_events: Option<Events>,
+    // event loop is running until Node is stopped
+    running: bool,
+
+    /// Members for EventHandler trait
+    // callbacks for local event handling, by event name
+    pub callbacks: BTreeMap<String, Vec<EventCallback>>,
+    // callback names for matching them with wildcards
+    pub callback_patterns: NameTrie,
+    // sender for triggering local callbacks from other threads
+    pub event_sender_chan: Sender<EventCommand>,
+    pub event_receiver_chan: Receiver<EventCommand>,
+
+    /// Tree state information known by this Node
+    pub graph: Graph,
+
+    /// Load balancing state for API clients subscribed to groups
+    pub balancer: Balancer,
+
+    /// Persistent queue for durable API clients
+    pub queue: EventQueue,
+
+    /// Timer for delayed tasks of this Node
+    pub timer: Timer<NodeTimeout>,
 
     /// parent address in case if we are doing something directly from command line
-    parent_address: String
+    parent_address: String,
+    // token of the connected parent Node, empty if we are not connected yet
+    parent_token: String,
+    // failed connection attempts to the parent since the last successful one
+    parent_attempts: u32,
+    // min and max delay in milliseconds between reconnect attempts
+    reconnect_min: u64,
+    reconnect_max: u64
 }
 
 
 impl Node {
     /// Making new node based on configurations
-    pub fn new(config: &NodeConfig) -> Node {
+    /// Services are not started until "init" is called
+    pub fn new(config: &NodeConfig) -> Result<Node, Error> {
         let (net_s, net_r) = channel::<NetworkCommand>();
+        let (event_s, event_r) = channel::<EventCommand>();
 
         let mut cpu_count = config.network.concurrency;
         if cpu_count == 0 {
             cpu_count = num_cpus::get();
         }
 
-        Node {
+        let token = if config.token.is_empty() { format!("{}", uuid::Uuid::new_v4()) } else { config.token.clone() };
+        let node_authenticator = make_node_authenticator(token.as_str(), &config.auth)?;
+        let net_tls = TcpTls::load(&config.network)?;
+        let net_authenticator = make_authenticator(&config.auth)?;
+
+        let poll = match Poll::new() {
+            Ok(p) => p,
+            Err(e) => return Err(Error::io("Unable to create POLL service for Node", e))
+        };
+
+        Ok(Node {
             value: config.value,
-            token: if config.token.len() == 0 { format!("{}", uuid::Uuid::new_v4()) } else { config.token.clone() },
+            graph: Graph::new(token.as_str(), config.value),
+            balancer: Balancer::new(config.balance),
+            queue: EventQueue::new(&config.queue),
+            timer: Timer::default(),
+            token,
             api_version: if config.api_version == 0 { DEFAULT_API_VERSION } else { config.api_version },
             connections: BTreeMap::new(),
             net_sender_chan: net_s,
             net_receiver_chan: net_r,
             net_tcp_handler_sender_chan: Vec::with_capacity(cpu_count),
             net_tcp_handler_index: 0,
-            net_tcp_server: Node::make_tcp_server(config.network.tcp_server_host.as_str()),
+            net_tcp_server: Node::make_tcp_server(config.network.tcp_server_host.as_str())?,
+            net_unix_server: Node::make_unix_server(config.network.unix_socket_path.as_str(), config.network.unix_socket_mode)?,
+            net_ws_server: Node::make_ws_server(config.network.ws_server_host.as_str())?,
             net_tcp_pending_connections: Slab::with_capacity(CONNECTION_COUNT_PRE_ALLOC),
-            poll: match Poll::new() {
-                Ok(p) => p,
-                Err(e) => {
-                    Log::error("Unable to create POLL service for Node", e.description());
-                    process::exit(1);
-                }
-            },
-            parent_address: config.parent_address.clone()
-        }
+            net_config: config.network.clone(),
+            net_recent_events: RecentEvents::new(config.network.dedup_cache),
+            net_dropped_events: DroppedEvents::default(),
+            net_tls,
+            net_authenticator,
+            net_node_authenticator: node_authenticator,
+            poll,
+            poll_events: Some(Events::with_capacity(EVENT_LOOP_EVENTS_SIZE)),
+            running: false,
+            callbacks: BTreeMap::new(),
+            callback_patterns: NameTrie::new(),
+            event_sender_chan: event_s,
+            event_receiver_chan: event_r,
+            parent_address: config.parent_address.clone(),
+            parent_token: String::new(),
+            parent_attempts: 0,
+            reconnect_min: config.network.reconnect_min,
+            reconnect_max: config.network.reconnect_max
+        })
     }
 
     /// Starting all services of Node and running event loop
-    pub fn start(&mut self) {
+    pub fn start(&mut self) -> Result<(), Error> {
+        self.init()?;
+        self.run()
+    }
+
+    /// Starting all services of Node without running event loop
+    /// After this Node is handling events with "run" or "poll_once"
+    pub fn init(&mut self) -> Result<(), Error> {
         // making networking available
-        self.init_networking();
+        self.init_networking()?;
+        self.init_event()?;
+        self.init_timer()?;
+        self.init_queue();
+
+        if !self.parent_address.is_empty() {
+            self.connect_parent();
+        }
+
+        self.running = true;
+        Ok(())
+    }
 
-        if self.parent_address.len() > 0 {
-            let address = self.parent_address.clone();
-            self.tcp_connect(address.as_str());
+    /// Running event loop until Node is stopped
+    pub fn run(&mut self) -> Result<(), Error> {
+        while self.running {
+            self.poll_once(None)?;
         }
 
-        // starting base event loop
-        // making events for handling 5K events at once
-        let mut events: Events = Events::with_capacity(EVENT_LOOP_EVENTS_SIZE);
-        loop {
-            let event_count = self.poll.poll(&mut events, None).unwrap();
-            if event_count == 0 {
+        Ok(())
+    }
+
+    /// Checking if Node is initialized and not stopped yet
+    #[inline(always)]
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Stopping event loop and TCP handlers of this Node
+    /// Event loop is stopping after handling current POLL events
+    pub fn stop(&mut self) {
+        if !self.running {
+            return;
+        }
+
+        self.running = false;
+        for chan in &self.net_tcp_handler_sender_chan {
+            let mut cmd = TcpHandlerCommand::new();
+            cmd.cmd = TcpHandlerCMD::Stop;
+            if let Err(e) = chan.send(cmd) {
+                Log::error("Unable to send stop command to TcpHandler", e.to_string().as_str());
+            }
+        }
+    }
+
+    /// Handling available POLL events once, waiting for them until given timeout
+    /// Returns count of handled POLL events
+    pub fn poll_once(&mut self, timeout: Option<Duration>) -> Result<usize, Error> {
+        let mut events = match self.poll_events.take() {
+            Some(e) => e,
+            None => Events::with_capacity(EVENT_LOOP_EVENTS_SIZE)
+        };
+
+        let event_count = match self.poll.poll(&mut events, timeout) {
+            Ok(n) => n,
+            Err(e) => {
+                self.poll_events = Some(events);
+                return Err(Error::io("Unable to poll Node events", e));
+            }
+        };
+
+        for event in events.iter() {
+            let (token, kind) = (event.token(), event.kind());
+
+            if token == NODE_TIMER_TOKEN {
+                self.timer_ready();
                 continue;
             }
 
-            for event in events.iter() {
-                let (token, kind) = (event.token(), event.kind());
+            if token == EVENT_RECEIVER_CHANNEL_TOKEN {
+                self.event_notify();
+                continue;
+            }
 
-                // if this is a networking event just moving to the next event
-                // otherwise we will probably check other block implementations
+            // if this is a networking event just moving to the next event
+            // otherwise we will probably check other block implementations
 //                if self.net_ready(token, kind) {
 //                    continue;
 //                }
-                self.net_ready(token, kind);
+            self.net_ready(token, kind);
+        }
+
+        self.poll_events = Some(events);
+        Ok(event_count)
+    }
+
+    fn init_timer(&mut self) -> Result<(), Error> {
+        match self.poll.register(&self.timer, NODE_TIMER_TOKEN, Ready::readable(), PollOpt::edge()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::io("Unable to register timer to Node POLL service", e))
+        }
+    }
+
+    /// Scheduling delayed task for current Node
+    pub fn set_timeout(&mut self, timeout: NodeTimeout, delay: Duration) {
+        if let Err(e) = self.timer.set_timeout(delay, timeout) {
+            Log::error("Unable to schedule Node timeout", e.to_string().as_str());
+        }
+    }
+
+    /// Handling all expired timeouts
+    fn timer_ready(&mut self) {
+        while let Some(timeout) = self.timer.poll() {
+            match timeout {
+                NodeTimeout::QueueRedelivery => self.queue_redeliver(),
+                NodeTimeout::ParentReconnect => self.connect_parent()
             }
         }
     }
 
+    /// Making connection to the parent Node
+    /// if connection couldn't be made, trying again later
+    fn connect_parent(&mut self) {
+        let address = self.parent_address.clone();
+        if !self.tcp_connect(address.as_str()) {
+            self.reconnect_parent();
+        }
+    }
+
+    /// Scheduling next connection attempt to the parent Node
+    /// using exponential backoff with random jitter, so that all children
+    /// wouldn't connect at the same time after parent restart
+    fn reconnect_parent(&mut self) {
+        let max = cmp::max(self.reconnect_max, self.reconnect_min);
+        let delay = self.reconnect_min.checked_shl(cmp::min(self.parent_attempts, 63))
+                                      .map(|d| cmp::min(d, max))
+                                      .unwrap_or(max);
+        // waiting random time between half and full delay
+        let delay = delay / 2 + rand::random::<u64>() % (delay / 2 + 1);
+        self.parent_attempts = self.parent_attempts.saturating_add(1);
+
+        Log::info("Reconnecting to the parent Node", format!("{} after {}ms", self.parent_address, delay).as_str());
+        self.set_timeout(NodeTimeout::ParentReconnect, Duration::from_millis(delay));
+    }
+
+    /// Calculating Path for given event using subscriptions from our Graph
+    /// so that event could be delivered to every subscriber of it
+    #[inline(always)]
+    pub fn resolve_path(&mut self, event: &Event) -> Path {
+        self.graph.resolve_path(event)
+    }
+
     /// Handling new connection here
-    pub fn on_new_connection(&mut self, token: &String, value: u64) {
+    /// Address is not empty if connection have been made by current Node
+    pub fn on_new_connection(&mut self, token: &String, value: u64, address: &str) {
         println!("Got New Connection -> {} {}", token, value);
+        if !address.is_empty() && address == self.parent_address {
+            self.parent_token = token.clone();
+            self.parent_attempts = 0;
+        }
+
+        // keeping directly connected Node as a part of our graph
+        self.graph.add_node(token, value);
+        self.graph.add_relation(&self.token, token);
+        self.gossip_join(token);
     }
 
     /// Handling new API connection here
     pub fn on_new_api_connection(&mut self, token: &String) {
         println!("Got New API Connection -> {}", token);
+        self.api_ready(token);
     }
 
     /// Handling new identity/channel from existing connection
@@ -126,9 +341,42 @@ impl Node {
         println!("Got New Connection Channel -> {}", token);
     }
 
+    /// Handling failed connection attempt to given address
+    pub fn on_connection_failed(&mut self, address: &str) {
+        Log::warn("Unable to connect to the Node", address);
+        if address == self.parent_address && self.parent_token.is_empty() {
+            self.reconnect_parent();
+        }
+    }
+
     /// Handling Connection Close Functionality
     pub fn on_connection_close(&mut self, token: &String) {
         println!("Connection Closed -> {}", token);
+        // parent is going to be connected again
+        // and our subscriptions would be announced during join
+        if !self.parent_token.is_empty() && *token == self.parent_token {
+            self.parent_token.clear();
+            self.reconnect_parent();
+        }
+
+        // API connections are not part of the tree
+        // so we only need to clean up their subscriptions
+        if self.connections.get(token).map(|c| c.value == 0).unwrap_or(false) {
+            self.api_close(token);
+            return;
+        }
+
+        let version = match self.graph.get(token) {
+            Some(n) => n.version,
+            None => return
+        };
+
+        // if this was a Node connection, then everything behind it
+        // is not reachable anymore from this Node
+        self.graph.rm_relation(&self.token, token);
+        let removed = self.graph.prune();
+        let left_version = if removed.contains(token) { Some(version) } else { None };
+        self.gossip_leave(token, left_version);
     }
 
     /// Handling Connection Close Functionality
@@ -142,6 +390,33 @@ impl Node {
     #[inline(always)]
     pub fn on_event_data(&mut self, token: &String, event: &Event) -> bool {
 //        println!("Got data from connection -> {} -> {}", token, event.from);
+        let from_api = match self.connections.get(token) {
+            Some(conn) => conn.value == 0,
+            None => return false
+        };
+
+        // Graph events are handled and forwarded by Gossip
+        // and they are accepted only from Node connections
+        if event.name.starts_with(GRAPH_EVENT_PREFIX) {
+            if !from_api {
+                self.on_gossip_event(token, event);
+            }
+
+            return false;
+        }
+
+        // control messages from API clients are handled only by current Node
+        if event.name.starts_with(API_EVENT_PREFIX) {
+            if from_api {
+                self.on_api_control(token, event);
+            }
+
+            return false;
+        }
+
+        // letting local callbacks to handle event before it's delivered or forwarded
+        self.trigger(event);
+
         true
     }
 }
\ No newline at end of file
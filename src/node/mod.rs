@@ -2,15 +2,28 @@
 
 extern crate mio;
 mod main;
+mod builder;
 
 pub use self::main::Node;
+pub use self::builder::{NodeBuilder, NodeHandle};
 
 
 use self::mio::Token;
-use std::u32::MAX as u32MAX;
-
-pub const NET_RECEIVER_CHANNEL_TOKEN: Token = Token((u32MAX - 1) as usize);
-pub const NET_TCP_SERVER_TOKEN: Token = Token((u32MAX - 2) as usize);
+pub const NET_RECEIVER_CHANNEL_TOKEN: Token = Token(u32::MAX as usize - 1);
+pub const NET_TCP_SERVER_TOKEN: Token = Token(u32::MAX as usize - 2);
+pub const NODE_TIMER_TOKEN: Token = Token(u32::MAX as usize - 3);
+pub const NET_TCP_TIMER_TOKEN: Token = Token(u32::MAX as usize - 4);
+pub const NET_UNIX_SERVER_TOKEN: Token = Token(u32::MAX as usize - 5);
+pub const NET_WS_SERVER_TOKEN: Token = Token(u32::MAX as usize - 6);
+pub const EVENT_RECEIVER_CHANNEL_TOKEN: Token = Token(u32::MAX as usize - 7);
 
 pub const EVENT_LOOP_EVENTS_SIZE: usize = 65000;
-pub const DEFAULT_API_VERSION: u32 = 1;
\ No newline at end of file
+pub const DEFAULT_API_VERSION: u32 = 2;
+
+/// Delayed tasks scheduled with Node timer
+pub enum NodeTimeout {
+    /// Checking persistent queue for events which are not acknowledged in time
+    QueueRedelivery,
+    /// Trying to connect again to the parent Node
+    ParentReconnect
+}
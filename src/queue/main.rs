@@ -0,0 +1,393 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use config::QueueConfig;
+use node::{Node, NodeTimeout};
+use network::Networking;
+use event::Event;
+use helper::{Log, NetHelper};
+use queue::{QueueWal, QUEUE_TICK_MS};
+use api::{ApiControl, SubscriptionMessage, API_QUEUE_EVENT};
+
+/// Persistent queue for API clients who requested durable delivery
+/// Events are kept in WAL file per API client until client will acknowledge them
+pub struct EventQueue {
+    // directory for keeping WAL files, empty path means that queue is disabled
+    path: PathBuf,
+    redelivery_timeout: Duration,
+    // after this count of delivery attempts event is moved to dead letter queue
+    // 0 means that event would be delivered until it is acknowledged
+    max_attempts: u32,
+    dead_letter: bool,
+    // records written to WAL file between syncing it to disk
+    sync_every: u32,
+
+    // WAL for each durable API client
+    // Key -> API client Token
+    wals: BTreeMap<String, QueueWal>,
+
+    // Events sent to API clients and waiting for acknowledgement
+    // Key -> API client Token
+    // Value -> entry ID with time of the last delivery and attempts count
+    inflight: BTreeMap<String, BTreeMap<u64, (Instant, u32)>>,
+}
+
+impl EventQueue {
+    pub fn new(config: &QueueConfig) -> EventQueue {
+        EventQueue {
+            path: PathBuf::from(config.path.as_str()),
+            redelivery_timeout: Duration::from_secs(config.redelivery_timeout),
+            max_attempts: config.max_attempts,
+            dead_letter: config.dead_letter,
+            sync_every: config.sync_every,
+            wals: BTreeMap::new(),
+            inflight: BTreeMap::new()
+        }
+    }
+
+    #[inline(always)]
+    pub fn enabled(&self) -> bool {
+        !self.path.as_os_str().is_empty()
+    }
+
+    #[inline(always)]
+    pub fn is_durable(&self, token: &str) -> bool {
+        self.wals.contains_key(token)
+    }
+
+    /// Opening all WAL files from queue directory
+    /// Returns durable API client tokens with their saved subscriptions data
+    pub fn load(&mut self) -> Vec<(String, Vec<u8>)> {
+        let mut clients = vec![];
+        if !self.enabled() {
+            return clients;
+        }
+
+        if let Err(e) = fs::create_dir_all(&self.path) {
+            Log::error("Unable to create queue directory", e.to_string().as_str());
+            return clients;
+        }
+
+        let entries = match fs::read_dir(&self.path) {
+            Ok(e) => e,
+            Err(e) => {
+                Log::error("Unable to read queue directory", e.to_string().as_str());
+                return clients;
+            }
+        };
+
+        for entry in entries.filter_map(|e| e.ok()) {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if !file_name.ends_with(".wal") {
+                continue;
+            }
+
+            let token = match EventQueue::decode_token(&file_name[..file_name.len() - 4]) {
+                Some(t) => t,
+                None => continue
+            };
+
+            match QueueWal::open(&entry.path(), self.sync_every) {
+                Ok(wal) => {
+                    let subscriptions = fs::read(self.file_path(&token, "subs")).unwrap_or_default();
+                    self.wals.insert(token.clone(), wal);
+                    clients.push((token, subscriptions));
+                }
+                Err(e) => Log::error("Unable to load queue WAL file", e.to_string().as_str())
+            }
+        }
+
+        clients
+    }
+
+    /// Making API client durable, so that his events would be kept in WAL file
+    pub fn enable(&mut self, token: &str) -> bool {
+        if !self.enabled() {
+            return false;
+        }
+
+        if self.wals.contains_key(token) {
+            return true;
+        }
+
+        if let Err(e) = fs::create_dir_all(&self.path) {
+            Log::error("Unable to create queue directory", e.to_string().as_str());
+            return false;
+        }
+
+        match QueueWal::open(&self.file_path(token, "wal"), self.sync_every) {
+            Ok(wal) => {
+                self.wals.insert(String::from(token), wal);
+                true
+            }
+            Err(e) => {
+                Log::error("Unable to load queue WAL file", e.to_string().as_str());
+                false
+            }
+        }
+    }
+
+    /// Removing API client WAL file with all pending events
+    pub fn disable(&mut self, token: &str) {
+        self.inflight.remove(token);
+        if let Some(wal) = self.wals.remove(token) {
+            wal.destroy();
+            let _ = fs::remove_file(self.file_path(token, "subs"));
+        }
+    }
+
+    /// Saving API client subscriptions, for restoring them after Node restart
+    pub fn save_subscriptions(&self, token: &str, data: &[u8]) {
+        if !self.wals.contains_key(token) {
+            return;
+        }
+
+        if let Err(e) = fs::write(self.file_path(token, "subs"), data) {
+            Log::error("Unable to save durable API client subscriptions", e.to_string().as_str());
+        }
+    }
+
+    #[inline(always)]
+    pub fn push(&mut self, token: &str, data: Vec<u8>) -> Option<u64> {
+        match self.wals.get_mut(token) {
+            Some(wal) => wal.push(data),
+            None => None
+        }
+    }
+
+    /// Removing acknowledged event from the queue
+    pub fn ack(&mut self, token: &str, id: u64) -> bool {
+        if let Some(inflight) = self.inflight.get_mut(token) {
+            inflight.remove(&id);
+        }
+
+        let acked = match self.wals.get_mut(token) {
+            Some(wal) => wal.ack(id),
+            None => false
+        };
+
+        if acked {
+            self.compact(token);
+        }
+
+        acked
+    }
+
+    /// Getting all pending event IDs for given API client
+    pub fn pending_ids(&self, token: &str) -> Vec<u64> {
+        match self.wals.get(token) {
+            Some(wal) => wal.pending().keys().cloned().collect(),
+            None => vec![]
+        }
+    }
+
+    /// Marking event as sent to API client, and getting data for it
+    /// Returns None if event is moved to dead letter queue because of attempts limit
+    pub fn sent(&mut self, token: &str, id: u64) -> Option<Vec<u8>> {
+        let attempts = {
+            let inflight = self.inflight.entry(String::from(token)).or_default();
+            let entry = inflight.entry(id).or_insert((Instant::now(), 0));
+            entry.0 = Instant::now();
+            entry.1 += 1;
+            entry.1
+        };
+
+        if self.max_attempts > 0 && attempts > self.max_attempts {
+            self.dead(token, id);
+            return None;
+        }
+
+        self.wals.get(token).and_then(|wal| wal.get(id)).cloned()
+    }
+
+    /// API client is disconnected, so nothing is in flight for him anymore
+    #[inline(always)]
+    pub fn offline(&mut self, token: &str) {
+        self.inflight.remove(token);
+    }
+
+    /// Syncing WAL records which are not synced yet because of sync batch size
+    pub fn sync(&mut self) {
+        for wal in self.wals.values_mut() {
+            wal.sync();
+        }
+    }
+
+    /// Getting events which are waiting for acknowledgement longer than redelivery timeout
+    pub fn expired(&self) -> Vec<(String, u64)> {
+        let mut expired = vec![];
+        for (token, inflight) in &self.inflight {
+            for (id, &(sent_at, _)) in inflight {
+                if sent_at.elapsed() >= self.redelivery_timeout {
+                    expired.push((token.clone(), *id));
+                }
+            }
+        }
+
+        expired
+    }
+
+    /// Starting API client WAL file from scratch if there is nothing pending
+    /// If compacted file couldn't be opened, we are loading it again,
+    /// and without WAL events for this API client are not queued until it is enabled again
+    fn compact(&mut self, token: &str) {
+        let path = match self.wals.get_mut(token) {
+            Some(wal) => match wal.compact() {
+                Ok(_) => return,
+                Err(e) => {
+                    Log::error("Unable to compact queue WAL file", e.to_string().as_str());
+                    wal.path().to_path_buf()
+                }
+            },
+            None => return
+        };
+
+        match QueueWal::open(&path, self.sync_every) {
+            Ok(wal) => {
+                self.wals.insert(String::from(token), wal);
+            }
+            Err(e) => {
+                Log::error("Unable to load queue WAL file", e.to_string().as_str());
+                self.wals.remove(token);
+            }
+        }
+    }
+
+    /// Moving event from API client queue to his dead letter file
+    fn dead(&mut self, token: &str, id: u64) {
+        if let Some(inflight) = self.inflight.get_mut(token) {
+            inflight.remove(&id);
+        }
+
+        let data = match self.wals.get_mut(token).and_then(|wal| wal.dead(id)) {
+            Some(d) => d,
+            None => return
+        };
+        self.compact(token);
+
+        Log::warn("Event reached delivery attempts limit", token);
+        if !self.dead_letter {
+            return;
+        }
+
+        // dead letter file is keeping 8 bytes ID, 4 bytes length and event data
+        let mut record = vec![0; 12 + data.len()];
+        NetHelper::u64_to_bytes(id, &mut record, 0);
+        NetHelper::u32_to_bytes(data.len() as u32, &mut record, 8);
+        record[12..].copy_from_slice(&data);
+
+        let result = OpenOptions::new().create(true).append(true)
+                                       .open(self.file_path(token, "dead"))
+                                       .and_then(|mut f| f.write_all(&record));
+        if let Err(e) = result {
+            Log::error("Unable to write event to dead letter file", e.to_string().as_str());
+        }
+    }
+
+    /// API client tokens could contain any characters
+    /// so we are keeping them as a HEX strings in file names
+    fn file_path(&self, token: &str, extension: &str) -> PathBuf {
+        let name: String = token.as_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        self.path.join(format!("{}.{}", name, extension))
+    }
+
+    fn decode_token(name: &str) -> Option<String> {
+        if name.len() % 2 == 1 {
+            return None;
+        }
+
+        let mut bytes = vec![];
+        for i in 0..(name.len() / 2) {
+            match u8::from_str_radix(&name[(i * 2)..(i * 2 + 2)], 16) {
+                Ok(b) => bytes.push(b),
+                Err(_) => return None
+            }
+        }
+
+        String::from_utf8(bytes).ok()
+    }
+}
+
+/// Queueing trait for delivering events to durable API clients
+pub trait Queueing {
+    /// Loading durable API clients from disk and starting redelivery timer
+    fn init_queue(&mut self);
+
+    /// Keeping event in API client queue and sending it if client is connected
+    fn queue_event(&mut self, token: &str, data: &[u8]);
+
+    /// Sending all pending events to reconnected API client
+    fn queue_replay(&mut self, token: &str);
+
+    /// Sending queued event with given ID to API client
+    fn queue_send(&mut self, token: &str, id: u64);
+
+    /// Sending again events which are not acknowledged in time
+    fn queue_redeliver(&mut self);
+}
+
+impl Queueing for Node {
+    fn init_queue(&mut self) {
+        if !self.queue.enabled() {
+            return;
+        }
+
+        // durable API clients are subscribed again, even if they are not connected yet
+        for (token, data) in self.queue.load() {
+            if let Ok(message) = SubscriptionMessage::from_bytes(&data) {
+                self.api_subscribe(&token, message.items, true);
+            }
+        }
+
+        self.set_timeout(NodeTimeout::QueueRedelivery, Duration::from_millis(QUEUE_TICK_MS));
+    }
+
+    fn queue_event(&mut self, token: &str, data: &[u8]) {
+        let id = match self.queue.push(token, Vec::from(data)) {
+            Some(id) => id,
+            None => return
+        };
+
+        if self.connections.contains_key(token) {
+            self.queue_send(token, id);
+        }
+    }
+
+    fn queue_replay(&mut self, token: &str) {
+        for id in self.queue.pending_ids(token) {
+            self.queue_send(token, id);
+        }
+    }
+
+    fn queue_send(&mut self, token: &str, id: u64) {
+        let data = match self.queue.sent(token, id) {
+            Some(d) => d,
+            None => return
+        };
+
+        // queued event is wrapped with 8 bytes ID, so that API client could acknowledge it
+        let mut ev = Event {
+            name: String::from(API_QUEUE_EVENT),
+            from: self.token.clone(),
+            data: vec![0; 8 + data.len()],
+            ..Event::default()
+        };
+        NetHelper::u64_to_bytes(id, &mut ev.data, 0);
+        ev.data[8..].copy_from_slice(&data);
+        self.send_to(&[String::from(token)], &ev);
+    }
+
+    fn queue_redeliver(&mut self) {
+        for (token, id) in self.queue.expired() {
+            self.queue_send(&token, id);
+        }
+
+        self.queue.sync();
+        self.set_timeout(NodeTimeout::QueueRedelivery, Duration::from_millis(QUEUE_TICK_MS));
+    }
+}
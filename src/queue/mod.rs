@@ -0,0 +1,8 @@
+mod wal;
+mod main;
+
+pub use self::wal::QueueWal;
+pub use self::main::{EventQueue, Queueing};
+
+/// Interval in milliseconds for checking events which are not acknowledged in time
+pub const QUEUE_TICK_MS: u64 = 1000;
@@ -0,0 +1,329 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use helper::{Log, NetHelper};
+use error::Error;
+
+/// Record types inside Write Ahead Log file
+const WAL_PUSH: u8 = 1;
+const WAL_ACK: u8 = 2;
+const WAL_DEAD: u8 = 3;
+const WAL_SEQ: u8 = 4;
+
+/// 1 byte record type, 8 bytes entry ID and 4 bytes data length
+const WAL_HEADER_LEN: usize = 13;
+
+/// Write Ahead Log for keeping events of single API client
+/// Each record is appended to the end of the file
+/// and file is replaced with a new one when there is no pending events anymore
+pub struct QueueWal {
+    file: File,
+    path: PathBuf,
+
+    // records written between syncing file to disk, 0 means syncing is left to operating system
+    sync_every: u32,
+    // records written after the last sync
+    unsynced: u32,
+
+    // events which are not acknowledged yet
+    // Key -> entry ID
+    // Value -> raw event data
+    pending: BTreeMap<u64, Vec<u8>>,
+
+    // ID for the next pushed event
+    next_id: u64,
+}
+
+impl QueueWal {
+    /// Opening WAL file and reading pending events from it
+    /// If file ends with partially written record, it is cut off
+    pub fn open(path: &Path, sync_every: u32) -> Result<QueueWal, Error> {
+        let mut file = match OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::io("Unable to open queue WAL file", e))
+        };
+
+        let mut data = vec![];
+        if let Err(e) = file.read_to_end(&mut data) {
+            return Err(Error::io("Unable to read queue WAL file", e));
+        }
+
+        let mut pending = BTreeMap::new();
+        let mut next_id = 1;
+        let mut offset = 0;
+        while offset + WAL_HEADER_LEN <= data.len() {
+            let kind = data[offset];
+            let id = NetHelper::bytes_to_u64(&data, offset + 1)?;
+            let len = NetHelper::bytes_to_u32(&data, offset + 9)? as usize;
+            if offset + WAL_HEADER_LEN + len > data.len() {
+                break;
+            }
+
+            match kind {
+                WAL_PUSH => {
+                    pending.insert(id, Vec::from(&data[(offset + WAL_HEADER_LEN)..(offset + WAL_HEADER_LEN + len)]));
+                }
+                WAL_ACK | WAL_DEAD => {
+                    pending.remove(&id);
+                }
+                // sequence record is keeping ID for the next pushed event
+                WAL_SEQ if id > next_id => next_id = id,
+                WAL_SEQ => {}
+                _ => break
+            }
+
+            if kind != WAL_SEQ && id >= next_id {
+                next_id = id + 1;
+            }
+            offset += WAL_HEADER_LEN + len;
+        }
+
+        if offset < data.len() {
+            Log::warn("Queue WAL file has partially written record, cutting it off", path.to_string_lossy().as_ref());
+            if let Err(e) = file.set_len(offset as u64).and_then(|_| file.sync_all()) {
+                return Err(Error::io("Unable to truncate queue WAL file", e));
+            }
+        }
+
+        if let Err(e) = file.seek(SeekFrom::End(0)) {
+            return Err(Error::io("Unable to seek queue WAL file", e));
+        }
+
+        Ok(QueueWal {
+            file,
+            path: path.to_path_buf(),
+            sync_every,
+            unsynced: 0,
+            pending,
+            next_id
+        })
+    }
+
+    #[inline(always)]
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    #[inline(always)]
+    pub fn pending(&self) -> &BTreeMap<u64, Vec<u8>> {
+        &self.pending
+    }
+
+    #[inline(always)]
+    pub fn get(&self, id: u64) -> Option<&Vec<u8>> {
+        self.pending.get(&id)
+    }
+
+    /// Appending event data to the log
+    /// Returns entry ID for later acknowledgement
+    pub fn push(&mut self, data: Vec<u8>) -> Option<u64> {
+        let id = self.next_id;
+        if !self.append(WAL_PUSH, id, &data) {
+            return None;
+        }
+
+        self.next_id += 1;
+        self.pending.insert(id, data);
+        Some(id)
+    }
+
+    /// Marking event as delivered
+    /// Returns false if we don't have pending event with given ID
+    pub fn ack(&mut self, id: u64) -> bool {
+        self.remove(WAL_ACK, id).is_some()
+    }
+
+    /// Marking event as moved to dead letter queue
+    /// Returns event data for writing it to dead letter file
+    pub fn dead(&mut self, id: u64) -> Option<Vec<u8>> {
+        self.remove(WAL_DEAD, id)
+    }
+
+    fn remove(&mut self, kind: u8, id: u64) -> Option<Vec<u8>> {
+        if !self.pending.contains_key(&id) || !self.append(kind, id, &[]) {
+            return None;
+        }
+
+        self.pending.remove(&id)
+    }
+
+    /// If there is nothing pending, log could be started from scratch
+    /// keeping only sequence record, so that IDs wouldn't be reused
+    /// New log is written to temporary file and renamed over the old one,
+    /// so that crash during compaction would leave one of them complete
+    /// Returns error only when old file is replaced, but we are unable to open the new one
+    pub fn compact(&mut self) -> Result<(), Error> {
+        if !self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let tmp_path = self.path.with_extension("compact");
+        let mut record = vec![0; WAL_HEADER_LEN];
+        record[0] = WAL_SEQ;
+        NetHelper::u64_to_bytes(self.next_id, &mut record, 1);
+
+        let replaced = File::create(&tmp_path)
+                        .and_then(|mut f| f.write_all(&record).and_then(|_| f.sync_all()))
+                        .and_then(|_| fs::rename(&tmp_path, &self.path));
+
+        // old file is still in place, so we could keep appending to it
+        if let Err(e) = replaced {
+            Log::error("Unable to compact queue WAL file", e.to_string().as_str());
+            let _ = fs::remove_file(&tmp_path);
+            return Ok(());
+        }
+
+        // old file is unlinked after rename, writing to it would lose records
+        if let Err(e) = QueueWal::sync_dir(&self.path) {
+            Log::warn("Unable to sync queue WAL directory", e.to_string().as_str());
+        }
+
+        match OpenOptions::new().append(true).open(&self.path) {
+            Ok(file) => {
+                self.file = file;
+                self.unsynced = 0;
+                Ok(())
+            }
+            Err(e) => Err(Error::io("Unable to reopen compacted queue WAL file", e))
+        }
+    }
+
+    /// Syncing directory of given file, so that renamed file would survive crash
+    fn sync_dir(path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+            _ => File::open(".")?.sync_all()
+        }
+    }
+
+    /// Syncing records which are written after the last sync
+    pub fn sync(&mut self) {
+        if self.unsynced == 0 {
+            return;
+        }
+
+        match self.file.sync_data() {
+            Ok(_) => self.unsynced = 0,
+            Err(e) => Log::error("Unable to sync queue WAL file", e.to_string().as_str())
+        }
+    }
+
+    fn append(&mut self, kind: u8, id: u64, data: &[u8]) -> bool {
+        let mut record = vec![0; WAL_HEADER_LEN + data.len()];
+        record[0] = kind;
+        NetHelper::u64_to_bytes(id, &mut record, 1);
+        NetHelper::u32_to_bytes(data.len() as u32, &mut record, 9);
+        record[WAL_HEADER_LEN..].copy_from_slice(data);
+
+        if let Err(e) = self.file.write_all(&record) {
+            Log::error("Unable to write record to queue WAL file", e.to_string().as_str());
+            return false;
+        }
+
+        self.unsynced += 1;
+        if self.sync_every > 0 && self.unsynced >= self.sync_every {
+            self.sync();
+        }
+
+        true
+    }
+
+    /// Removing WAL file completely
+    pub fn destroy(self) {
+        if let Err(e) = ::std::fs::remove_file(&self.path) {
+            Log::error("Unable to remove queue WAL file", e.to_string().as_str());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// Unique file path inside temporary directory, removed when test is done
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> TempPath {
+            let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+            TempPath(env::temp_dir().join(format!("treescale-{}-{}-{}.wal", name, process::id(), nanos)))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+            let _ = fs::remove_file(self.0.with_extension("compact"));
+        }
+    }
+
+    #[test]
+    fn append_and_replay() {
+        let path = TempPath::new("replay");
+        {
+            let mut wal = QueueWal::open(&path.0, 1).unwrap();
+            assert_eq!(wal.push(b"one".to_vec()), Some(1));
+            assert_eq!(wal.push(b"two".to_vec()), Some(2));
+            assert_eq!(wal.push(b"three".to_vec()), Some(3));
+            assert!(wal.ack(1));
+            assert!(!wal.ack(1));
+            assert_eq!(wal.dead(3), Some(b"three".to_vec()));
+        }
+
+        let mut wal = QueueWal::open(&path.0, 1).unwrap();
+        assert_eq!(wal.pending().keys().cloned().collect::<Vec<u64>>(), vec![2]);
+        assert_eq!(wal.get(2), Some(&b"two".to_vec()));
+        assert_eq!(wal.push(b"four".to_vec()), Some(4));
+    }
+
+    #[test]
+    fn partial_record_is_cut_off() {
+        let path = TempPath::new("partial");
+        {
+            let mut wal = QueueWal::open(&path.0, 0).unwrap();
+            wal.push(b"one".to_vec());
+            wal.push(b"two".to_vec());
+        }
+
+        let len = fs::metadata(&path.0).unwrap().len();
+        OpenOptions::new().write(true).open(&path.0).unwrap().set_len(len - 1).unwrap();
+
+        let wal = QueueWal::open(&path.0, 0).unwrap();
+        assert_eq!(wal.pending().len(), 1);
+        assert_eq!(fs::metadata(&path.0).unwrap().len(), (WAL_HEADER_LEN + 3) as u64);
+    }
+
+    #[test]
+    fn compact_keeps_sequence() {
+        let path = TempPath::new("compact");
+        {
+            let mut wal = QueueWal::open(&path.0, 1).unwrap();
+            wal.push(b"one".to_vec());
+            wal.push(b"two".to_vec());
+            wal.ack(1);
+            wal.ack(2);
+            wal.compact().unwrap();
+
+            // only sequence record is left after the last pending event is removed
+            assert!(wal.pending().is_empty());
+            assert_eq!(fs::metadata(&path.0).unwrap().len(), WAL_HEADER_LEN as u64);
+            assert!(!path.0.with_extension("compact").exists());
+            assert_eq!(wal.push(b"three".to_vec()), Some(3));
+        }
+
+        let mut wal = QueueWal::open(&path.0, 1).unwrap();
+        assert_eq!(wal.pending().keys().cloned().collect::<Vec<u64>>(), vec![3]);
+        assert!(wal.ack(3));
+
+        let mut wal = QueueWal::open(&path.0, 1).unwrap();
+        assert!(wal.pending().is_empty());
+        assert_eq!(wal.push(b"four".to_vec()), Some(4));
+    }
+}